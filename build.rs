@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Only the client is used in Rust, the server lives in python/robot_grpc_server.py
+    tonic_build::configure()
+        .build_server(false)
+        .compile(&["proto/robot_sim.proto"], &["proto"])?;
+    Ok(())
+}
@@ -11,7 +11,56 @@ class FieldPose(BaseModel):
     rotation: float
 
 
+class TrajectoryState(BaseModel):
+    time: float
+    pose: FieldPose
+    # Where the robot faces, the pose rotation is the direction of travel
+    holonomic_rotation: float
+    velocity: float
+    acceleration: float
+    curvature: float
+
+
+class RectangleShape(BaseModel):
+    min: FieldPosition
+    max: FieldPosition
+
+
+class ZoneShape(BaseModel):
+    # Sent as a tagged enum, exactly one of these is set
+    Rectangle: RectangleShape | None = None
+    Polygon: list[FieldPosition] | None = None
+
+    def vertices(self) -> list[FieldPosition]:
+        if self.Rectangle is not None:
+            lo, hi = self.Rectangle.min, self.Rectangle.max
+            return [lo, FieldPosition(x=hi.x, y=lo.y), hi, FieldPosition(x=lo.x, y=hi.y)]
+        return self.Polygon or []
+
+
+class ConstraintZone(BaseModel):
+    shape: ZoneShape
+    # None leaves that limit to the trajectory config
+    max_velocity: float | None = None
+    max_acceleration: float | None = None
+
+
+class TrajectoryConfig(BaseModel):
+    # SI units, per path
+    max_velocity: float = 5
+    max_acceleration: float = 1
+    max_angular_velocity: float = 3
+
+
 class Trajectory(BaseModel):
     start: FieldPose
     points: list[FieldPosition]
     end: FieldPose
+    start_velocity: float = 0
+    end_velocity: float = 0
+    # One per point, None leaves the point unconstrained
+    point_max_velocities: list[float | None] = []
+    # One per point, None lets the heading carry on towards the next target
+    rotation_targets: list[float | None] = []
+    zones: list[ConstraintZone] = []
+    config: TrajectoryConfig = TrajectoryConfig()
@@ -1,38 +1,122 @@
 import json
 import socket
+import struct
 
-from pydantic import parse_obj_as
+from pydantic import ValidationError, parse_obj_as
 
-from python.robot_comm_models import FieldPosition, FieldPose, Trajectory
+from python.robot_comm_models import Trajectory
 from python.robot_sim_server import gen_trajectory
 
 HOST = "127.0.0.1"
 PORT = 65426
 
-with socket.socket(socket.AF_INET, socket.SOCK_STREAM) as s:
-    s.bind((HOST, PORT))
-    s.listen()
-    conn, addr = s.accept()
-    with conn:
-        print(f"Connected to client.")
-        while True:
-            data = conn.recv(2048)
-            if not data:
-                break
+# Must match PROTOCOL_VERSION in src/robot_connection/tcp.rs
+PROTOCOL_VERSION = 2
 
-            d = json.loads(data)
+MAX_FRAME_LEN = 16 * 1024 * 1024
 
-            # print(d)
+# Frames are a big-endian u32 byte length followed by a JSON payload
+FRAME_HEADER = struct.Struct(">I")
 
-            t: Trajectory = parse_obj_as(Trajectory, d)
 
-            # print(t)
+class ProtocolError(Exception):
+    pass
 
-            trajectory = gen_trajectory(t.start, t.points, t.end)
 
-            if trajectory is None:
-                trajectory = []
+def recv_exact(conn: socket.socket, n: int) -> bytes | None:
+    buf = b""
+    while len(buf) < n:
+        chunk = conn.recv(n - len(buf))
+        if not chunk:
+            return None
+        buf += chunk
+    return buf
+
+
+def recv_frame(conn: socket.socket) -> bytes | None:
+    header = recv_exact(conn, FRAME_HEADER.size)
+    if header is None:
+        return None
+    (length,) = FRAME_HEADER.unpack(header)
+    if length > MAX_FRAME_LEN:
+        raise ProtocolError(f"frame of {length} bytes exceeds limit")
+    return recv_exact(conn, length)
 
-            conn.sendall(
-                json.dumps([k.dict() for k in trajectory]).encode()
+
+def send_frame(conn: socket.socket, payload: dict):
+    data = json.dumps(payload).encode()
+    conn.sendall(FRAME_HEADER.pack(len(data)) + data)
+
+
+def error(message: str) -> dict:
+    return {"type": "Error", "message": message}
+
+
+def handle_request(body: dict) -> dict:
+    match body.get("type"):
+        case "GenerateTrajectory":
+            t: Trajectory = parse_obj_as(Trajectory, body["trajectory"])
+
+            trajectory = gen_trajectory(
+                t.start, t.points, t.end, t.start_velocity, t.end_velocity, t.point_max_velocities, t.zones, t.config,
+                t.rotation_targets
             )
+
+            if trajectory is None:
+                return error("unable to generate trajectory through the given waypoints")
+
+            return {"type": "Trajectory", "states": [k.dict() for k in trajectory]}
+        case "Ping":
+            return {"type": "Pong"}
+        case other:
+            return error(f"unknown request type {other!r}")
+
+
+def handle_frame(frame: bytes) -> dict:
+    try:
+        message = json.loads(frame)
+    except json.JSONDecodeError as e:
+        return {"version": PROTOCOL_VERSION, "id": None, "body": error(f"malformed message: {e}")}
+
+    request_id = message.get("id")
+
+    if message.get("version") != PROTOCOL_VERSION:
+        body = error(f"client speaks protocol version {message.get('version')}, expected {PROTOCOL_VERSION}")
+    else:
+        try:
+            body = handle_request(message.get("body", {}))
+        except (KeyError, ValidationError) as e:
+            body = error(f"invalid request: {e}")
+        except Exception as e:
+            body = error(f"internal error: {e}")
+
+    return {"version": PROTOCOL_VERSION, "id": request_id, "body": body}
+
+
+def serve_client(conn: socket.socket):
+    with conn:
+        print(f"Connected to client.")
+        while True:
+            try:
+                frame = recv_frame(conn)
+            except ProtocolError as e:
+                print(f"Dropping client: {e}")
+                break
+            except ConnectionError:
+                break
+
+            if frame is None:
+                break
+
+            send_frame(conn, handle_frame(frame))
+        print(f"Client disconnected.")
+
+
+with socket.socket(socket.AF_INET, socket.SOCK_STREAM) as s:
+    s.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+    s.bind((HOST, PORT))
+    s.listen()
+    # The sim reconnects whenever it loses the connection, so keep accepting
+    while True:
+        conn, addr = s.accept()
+        serve_client(conn)
@@ -0,0 +1,134 @@
+# Generate the protobuf modules first, from the repository root:
+#   python -m grpc_tools.protoc -Iproto --python_out=python --grpc_python_out=python proto/robot_sim.proto
+import sys
+import threading
+import time
+from concurrent import futures
+from pathlib import Path
+
+import grpc
+
+# The generated robot_sim_pb2_grpc imports robot_sim_pb2 as a top level module
+sys.path.append(str(Path(__file__).parent))
+
+import robot_sim_pb2 as pb
+import robot_sim_pb2_grpc as pb_grpc
+
+from python.robot_comm_models import ConstraintZone, FieldPosition, FieldPose, TrajectoryConfig, TrajectoryState, ZoneShape
+from python.robot_sim_server import gen_trajectory
+
+ADDRESS = "127.0.0.1:50051"
+
+STATE_PERIOD_S = 0.02
+
+
+def to_field_position(p: pb.FieldPosition) -> FieldPosition:
+    return FieldPosition(x=p.x, y=p.y)
+
+
+def to_field_pose(p: pb.FieldPose) -> FieldPose:
+    return FieldPose(translation=to_field_position(p.translation), rotation=p.rotation)
+
+
+# Rectangles arrive as their four corners, zero leaves a limit unset
+def to_constraint_zone(z: pb.ConstraintZone) -> ConstraintZone:
+    return ConstraintZone(
+        shape=ZoneShape(Polygon=[to_field_position(v) for v in z.vertices]),
+        max_velocity=z.max_velocity or None,
+        max_acceleration=z.max_acceleration or None
+    )
+
+
+# Clients that don't send a config get the defaults
+def to_trajectory_config(t: pb.Trajectory) -> TrajectoryConfig:
+    if not t.HasField("config"):
+        return TrajectoryConfig()
+    return TrajectoryConfig(
+        max_velocity=t.config.max_velocity,
+        max_acceleration=t.config.max_acceleration,
+        max_angular_velocity=t.config.max_angular_velocity
+    )
+
+
+def from_trajectory_state(s: TrajectoryState) -> pb.TrajectoryState:
+    return pb.TrajectoryState(
+        time=s.time,
+        pose=pb.FieldPose(translation=pb.FieldPosition(x=s.pose.translation.x, y=s.pose.translation.y), rotation=s.pose.rotation),
+        holonomic_rotation=s.holonomic_rotation,
+        velocity=s.velocity,
+        acceleration=s.acceleration,
+        curvature=s.curvature
+    )
+
+
+class RobotSimServicer(pb_grpc.RobotSimServicer):
+    def __init__(self):
+        self.lock = threading.Lock()
+        self.start_time = time.monotonic()
+        self.mode = pb.ROBOT_MODE_DISABLED
+        self.routine = 0
+        self.pose = pb.FieldPose(translation=pb.FieldPosition(x=0, y=0), rotation=0)
+
+    def GenerateTrajectory(self, request: pb.GenerateTrajectoryRequest, context: grpc.ServicerContext):
+        t = request.trajectory
+        point_max_velocities: list[float | None] = [None] * len(t.points)
+        for limit in t.point_velocity_limits:
+            if limit.point < len(t.points):
+                point_max_velocities[limit.point] = limit.max_velocity
+        rotation_targets: list[float | None] = [None] * len(t.points)
+        for target in t.rotation_targets:
+            if target.point < len(t.points):
+                rotation_targets[target.point] = target.rotation
+
+        trajectory = gen_trajectory(
+            to_field_pose(t.start),
+            [to_field_position(p) for p in t.points],
+            to_field_pose(t.end),
+            t.start_velocity,
+            t.end_velocity,
+            point_max_velocities,
+            [to_constraint_zone(z) for z in t.zones],
+            to_trajectory_config(t),
+            rotation_targets
+        )
+
+        if trajectory is None:
+            context.abort(grpc.StatusCode.INVALID_ARGUMENT, "unable to generate trajectory through the given waypoints")
+
+        return pb.GenerateTrajectoryResponse(states=[from_trajectory_state(s) for s in trajectory])
+
+    def StreamRobotState(self, request: pb.StreamRobotStateRequest, context: grpc.ServicerContext):
+        while context.is_active():
+            with self.lock:
+                state = pb.RobotState(
+                    mode=self.mode,
+                    routine=self.routine,
+                    pose=self.pose,
+                    timestamp=time.monotonic() - self.start_time
+                )
+            yield state
+            time.sleep(STATE_PERIOD_S)
+
+    def SetRobotMode(self, request: pb.SetRobotModeRequest, context: grpc.ServicerContext):
+        if request.mode == pb.ROBOT_MODE_UNSPECIFIED:
+            context.abort(grpc.StatusCode.INVALID_ARGUMENT, "robot mode must be specified")
+
+        with self.lock:
+            self.mode = request.mode
+            self.routine = request.routine
+
+        print(f"Robot mode set to {pb.RobotMode.Name(request.mode)}")
+        return pb.SetRobotModeResponse()
+
+
+def serve():
+    server = grpc.server(futures.ThreadPoolExecutor(max_workers=8))
+    pb_grpc.add_RobotSimServicer_to_server(RobotSimServicer(), server)
+    server.add_insecure_port(ADDRESS)
+    server.start()
+    print(f"Listening on {ADDRESS}")
+    server.wait_for_termination()
+
+
+if __name__ == "__main__":
+    serve()
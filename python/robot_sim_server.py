@@ -1,26 +1,64 @@
 from robotpy_toolkit_7407.utils.units import rad, m, s
 from wpimath.geometry import Translation2d
 
-from python.robot_comm_models import FieldPose, FieldPosition
+from python.robot_comm_models import ConstraintZone, FieldPose, FieldPosition, TrajectoryConfig, TrajectoryState
 from python.swerve_sim_trajectory import SimTrajectory, TrajectoryEndpoint
 
 
-def gen_trajectory(start_pose: FieldPose, waypoints: list[FieldPosition], end_pose: FieldPose) -> list[FieldPosition] | None:
+def gen_trajectory(start_pose: FieldPose, waypoints: list[FieldPosition], end_pose: FieldPose,
+                   start_velocity: float = 0, end_velocity: float = 0,
+                   point_max_velocities: list[float | None] | None = None,
+                   zones: list[ConstraintZone] | None = None,
+                   config: TrajectoryConfig = TrajectoryConfig(),
+                   rotation_targets: list[float | None] | None = None) -> list[TrajectoryState] | None:
+    points = list(Translation2d(w.x, w.y) for w in waypoints)
+    point_limits = [
+        (point, vel * m/s)
+        for point, vel in zip(points, point_max_velocities or [])
+        if vel is not None
+    ]
+    zone_limits = [
+        (
+            [Translation2d(v.x, v.y) for v in zone.shape.vertices()],
+            None if zone.max_velocity is None else zone.max_velocity * m/s,
+            None if zone.max_acceleration is None else zone.max_acceleration * m/(s*s)
+        )
+        for zone in zones or []
+    ]
+    targets = [
+        (point, rotation * rad)
+        for point, rotation in zip(points, rotation_targets or [])
+        if rotation is not None
+    ]
+    start = TrajectoryEndpoint(start_pose.translation.x * m, start_pose.translation.y * m, start_pose.rotation * rad,
+                               start_velocity * m/s)
+    end = TrajectoryEndpoint(end_pose.translation.x * m, end_pose.translation.y * m, end_pose.rotation * rad,
+                             end_velocity * m/s)
     trajectory = SimTrajectory.generate_trajectory(
-        TrajectoryEndpoint(start_pose.translation.x * m, start_pose.translation.y * m, start_pose.rotation * rad),
-        list(Translation2d(w.x, w.y) for w in waypoints),
-        TrajectoryEndpoint(end_pose.translation.x * m, end_pose.translation.y * m, end_pose.rotation * rad),
-        5 * m/s,
-        1 * m/(s*s)
+        start,
+        points,
+        end,
+        config.max_velocity * m/s,
+        config.max_acceleration * m/(s*s),
+        point_limits,
+        zone_limits,
+        targets,
+        config.max_angular_velocity * rad/s
     )
     if trajectory is None:
         return None
-    samples = 300
-    step = trajectory.totalTime() / samples
-    t = 0
-    points = []
-    while t <= trajectory.totalTime():
-        sample = trajectory.sample(t)
-        points.append(FieldPosition(x=sample.pose.X(), y=sample.pose.Y()))
-        t += step
-    return points
+    headings = SimTrajectory.heading_profile(trajectory, start, targets, end)
+    return [
+        TrajectoryState(
+            time=state.t,
+            pose=FieldPose(
+                translation=FieldPosition(x=state.pose.X(), y=state.pose.Y()),
+                rotation=state.pose.rotation().radians()
+            ),
+            holonomic_rotation=headings.heading(state.pose),
+            velocity=state.velocity,
+            acceleration=state.acceleration,
+            curvature=state.curvature
+        )
+        for state in trajectory.states()
+    ]
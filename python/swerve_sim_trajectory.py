@@ -1,16 +1,119 @@
+import bisect
+import math
 from dataclasses import dataclass
 
 from robotpy_toolkit_7407.unum import Unum
-from wpimath.geometry import Pose2d, Translation2d
-from wpimath.trajectory import TrajectoryGenerator, TrajectoryConfig, Trajectory
+from wpimath.geometry import Pose2d, Rotation2d, Translation2d
+from wpimath.trajectory import TrajectoryGenerator, TrajectoryConfig, TrajectoryParameterizer, Trajectory
+from wpimath.trajectory.constraint import EllipticalRegionConstraint, MaxVelocityConstraint, TrajectoryConstraint
 
 from robotpy_toolkit_7407.utils.units import m, rad, s
 
+# A point's velocity limit applies this close to it, same as the Rust generator
+POINT_CONSTRAINT_RADIUS = 0.1 * m
+
 
 def translation(x: Unum, y: Unum) -> Translation2d:
     return Translation2d(x.asNumber(m), y.asNumber(m))
 
 
+class PolygonRegionConstraint(TrajectoryConstraint):
+    """Caps velocity and acceleration while the robot is inside a polygon, same even-odd test as the Rust generator"""
+
+    def __init__(self, vertices: list[Translation2d], max_vel: Unum | None, max_accel: Unum | None):
+        super().__init__()
+        self.vertices = [(v.X(), v.Y()) for v in vertices]
+        self.max_vel = float("inf") if max_vel is None else max_vel.asNumber(m/s)
+        self.max_accel = None if max_accel is None else max_accel.asNumber(m/(s*s))
+
+    def contains(self, pose: Pose2d) -> bool:
+        px, py = pose.X(), pose.Y()
+        inside = False
+        for i, (xi, yi) in enumerate(self.vertices):
+            xj, yj = self.vertices[i - 1]
+            if (yi > py) != (yj > py) and px < (xj - xi) * (py - yi) / (yj - yi) + xi:
+                inside = not inside
+        return inside
+
+    def maxVelocity(self, pose: Pose2d, curvature: float, velocity: float) -> float:
+        return self.max_vel if self.contains(pose) else float("inf")
+
+    def minMaxAcceleration(self, pose: Pose2d, curvature: float, speed: float) -> TrajectoryConstraint.MinMax:
+        limits = TrajectoryConstraint.MinMax()
+        if self.max_accel is not None and self.contains(pose):
+            limits.minAcceleration = -self.max_accel
+            limits.maxAcceleration = self.max_accel
+        return limits
+
+
+class HeadingProfile:
+    """Robot heading along a generated path, interpolated by distance between rotation targets like the Rust generator.
+    The path tangent doesn't matter, a swerve can face anywhere while it drives."""
+
+    def __init__(self, trajectory: Trajectory, start_heading: float, targets: list[tuple[Translation2d, float]],
+                 end_heading: float):
+        states = trajectory.states()
+        self.positions = [s.pose.translation() for s in states]
+        self.distances = [0.0]
+        for a, b in zip(self.positions, self.positions[1:]):
+            self.distances.append(self.distances[-1] + a.distance(b))
+        # Constraints and callers pass the poses of these same states, so lookups are exact
+        self.indices = {(p.X(), p.Y()): i for i, p in enumerate(self.positions)}
+
+        # WPILib doesn't say where the waypoints ended up, so each target goes to the nearest state after the last
+        knots = [(0.0, start_heading)]
+        idx = 0
+        for point, heading in targets:
+            idx = min(range(idx, len(self.positions)), key=lambda i: self.positions[i].distance(point))
+            knots.append((self.distances[idx], heading))
+        knots.append((self.distances[-1], end_heading))
+
+        # Each target is reached the short way round from the one before
+        self.knots = [knots[0]]
+        for distance, heading in knots[1:]:
+            previous = self.knots[-1][1]
+            self.knots.append((distance, previous + math.remainder(heading - previous, math.tau)))
+        self.knot_distances = [distance for distance, _ in self.knots]
+
+    def _segment(self, distance: float) -> tuple[float, float, float]:
+        """Start distance, start heading and radians per meter of the segment containing distance"""
+        idx = bisect.bisect_left(self.knot_distances, distance, 1, len(self.knots) - 1)
+        (d0, h0), (d1, h1) = self.knots[idx - 1], self.knots[idx]
+        rate = (h1 - h0) / (d1 - d0) if d1 > d0 else 0
+        return d0, h0, rate
+
+    def _distance(self, pose: Pose2d) -> float:
+        translation = pose.translation()
+        idx = self.indices.get((translation.X(), translation.Y()))
+        if idx is None:
+            idx = min(range(len(self.positions)), key=lambda i: self.positions[i].distance(translation))
+        return self.distances[idx]
+
+    def heading(self, pose: Pose2d) -> float:
+        distance = self._distance(pose)
+        d0, h0, rate = self._segment(distance)
+        return math.remainder(h0 + rate * (distance - d0), math.tau)
+
+    def rate(self, pose: Pose2d) -> float:
+        return abs(self._segment(self._distance(pose))[2])
+
+
+class HeadingRateConstraint(TrajectoryConstraint):
+    """Slows the path down where the heading has to turn faster than the max angular velocity"""
+
+    def __init__(self, profile: HeadingProfile, max_angular_vel: Unum):
+        super().__init__()
+        self.profile = profile
+        self.max_omega = max_angular_vel.asNumber(rad/s)
+
+    def maxVelocity(self, pose: Pose2d, curvature: float, velocity: float) -> float:
+        rate = self.profile.rate(pose)
+        return self.max_omega / rate if rate > 0 else float("inf")
+
+    def minMaxAcceleration(self, pose: Pose2d, curvature: float, speed: float) -> TrajectoryConstraint.MinMax:
+        return TrajectoryConstraint.MinMax()
+
+
 @dataclass
 class TrajectoryEndpoint:
     x: Unum = 0 * m
@@ -25,15 +128,52 @@ class TrajectoryEndpoint:
 class SimTrajectory:
     @staticmethod
     def generate_trajectory(start: TrajectoryEndpoint, waypoints: list[Translation2d], end: TrajectoryEndpoint,
-                            max_vel: Unum, max_accel: Unum) -> Trajectory | None:
+                            max_vel: Unum, max_accel: Unum,
+                            point_limits: list[tuple[Translation2d, Unum]] = (),
+                            zone_limits: list[tuple[list[Translation2d], Unum | None, Unum | None]] = (),
+                            rotation_targets: list[tuple[Translation2d, Unum]] = (),
+                            max_angular_vel: Unum | None = None) -> Trajectory | None:
         config = TrajectoryConfig(max_vel.asNumber(m/s), max_accel.asNumber(m/(s*s)))
         config.setStartVelocity(start.vel.asNumber(m/s))
         config.setEndVelocity(end.vel.asNumber(m/s))
+        diameter = 2 * POINT_CONSTRAINT_RADIUS.asNumber(m)
+        constraints = [
+            EllipticalRegionConstraint(point, diameter, diameter, Rotation2d(), MaxVelocityConstraint(vel.asNumber(m/s)))
+            for point, vel in point_limits
+        ] + [
+            PolygonRegionConstraint(vertices, vel, accel)
+            for vertices, vel, accel in zone_limits
+        ]
+        for constraint in constraints:
+            config.addConstraint(constraint)
         try:
             t = TrajectoryGenerator.generateTrajectory(start.as_pose(), waypoints, end.as_pose(), config)
             if t.totalTime() == 0:
                 return None
+            # Constraints can't change the path's shape, so the first pass says where the heading turns
+            # and its states are timed again with the turn rate limit instead of generating the splines twice
+            if max_angular_vel is not None:
+                profile = SimTrajectory.heading_profile(t, start, rotation_targets, end)
+                t = TrajectoryParameterizer.timeParameterizeTrajectory(
+                    [(state.pose, state.curvature) for state in t.states()],
+                    constraints + [HeadingRateConstraint(profile, max_angular_vel)],
+                    start.vel.asNumber(m/s),
+                    end.vel.asNumber(m/s),
+                    max_vel.asNumber(m/s),
+                    max_accel.asNumber(m/(s*s)),
+                    False
+                )
             return t
         except:
             return None
 
+    @staticmethod
+    def heading_profile(trajectory: Trajectory, start: TrajectoryEndpoint,
+                        rotation_targets: list[tuple[Translation2d, Unum]], end: TrajectoryEndpoint) -> HeadingProfile:
+        return HeadingProfile(
+            trajectory,
+            start.angle.asNumber(rad),
+            [(point, heading.asNumber(rad)) for point, heading in rotation_targets],
+            end.angle.asNumber(rad)
+        )
+
@@ -1,27 +1,71 @@
-use bevy::app::Events;
 use bevy::prelude::*;
-use bevy_prototype_lyon::prelude::*;
 use uom::ConstZero;
 use uom::si::angle::Angle;
-use uom::si::f32::Length;
+use uom::si::acceleration::meter_per_second_squared;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f32::{Acceleration, AngularVelocity, Length, Velocity};
 use uom::si::length::meter;
-use crate::auto_pathing::waypoints::{FieldWaypointList, spawn_waypoint, Waypoint};
-use crate::field::{FieldPose, FieldPosition};
-use crate::{Layout, Trajectory};
-use crate::auto_pathing::trajectory::TrajectoryID;
-use crate::field::render::FieldZ;
-use crate::layout::event::LayoutChangedEvent;
+use uom::si::velocity::meter_per_second;
+use crate::auto_pathing::history::{Edit, EditHistory};
+use crate::auto_pathing::path_formats;
+use crate::auto_pathing::routine_file::{self, RoutineFilePath};
+use crate::auto_pathing::snapping::Snapping;
+use crate::auto_pathing::generator::TrajectoryConfig;
+use crate::auto_pathing::waypoints::{CursorState, CursorTool, FieldWaypointList, PathConstraints, replace_routines, RoutineEntityFilter, Waypoint};
+use crate::auto_pathing::zones::{ConstraintZones, ZoneEditor};
+use crate::field::{Field, FieldPose, FieldPosition};
+use crate::auto_pathing::trajectory::{TimedTrajectory, TrajectoryID};
 use crate::layout::render::FONT_SIZE;
+use crate::layout::ui::{self, PanelRoot};
 
 #[derive(Component)]
 pub struct ConfigRoot;
 
+#[derive(Copy, Clone, PartialEq)]
 pub enum ConfigButtonAction {
     AddWaypoint,
     RemoveWaypoint,
     IncrementPathIdx,
     DecrementPathIdx,
-    AddPath
+    AddPath,
+    Open,
+    Save,
+    SaveAs,
+    Import,
+    ExportPathPlanner,
+    ExportPathWeaver,
+    ExportTrajectoryJson,
+    ExportTrajectoryCsv,
+    ToggleGridSnap,
+    ToggleFeatureSnap,
+    ToggleHeadingSnap,
+    CoarserGrid,
+    FinerGrid,
+    RaiseLimit(PathLimit),
+    LowerLimit(PathLimit),
+    DrawRectangleZone,
+    DrawPolygonZone,
+    DeleteZone,
+    RaiseZoneLimit(ZoneLimit),
+    LowerZoneLimit(ZoneLimit)
+}
+
+// Limits of the active path, the waypoint one belongs to the selected interior waypoint
+#[derive(Copy, Clone, PartialEq)]
+pub enum PathLimit {
+    MaxVelocity,
+    MaxAcceleration,
+    MaxAngularVelocity,
+    StartVelocity,
+    EndVelocity,
+    WaypointVelocity
+}
+
+// Limits of the selected zone
+#[derive(Copy, Clone, PartialEq)]
+pub enum ZoneLimit {
+    Velocity,
+    Acceleration
 }
 
 #[derive(Component)]
@@ -29,242 +73,497 @@ pub struct ConfigButton {
     action: ConfigButtonAction
 }
 
-#[derive(Component)]
+#[derive(Component, Copy, Clone)]
 pub enum ConfigText {
-    RoutineNumber
+    RoutineNumber,
+    File,
+    Snap,
+    GridSize,
+    MaxVelocity,
+    MaxAcceleration,
+    MaxAngularVelocity,
+    StartVelocity,
+    EndVelocity,
+    WaypointVelocity,
+    Zone,
+    ZoneVelocity,
+    ZoneAcceleration
 }
 
-const NORMAL_BUTTON: Color = Color::rgb(0.35, 0.35, 0.35);
-const HOVERED_BUTTON: Color = Color::rgb(0.45, 0.45, 0.45);
-const PRESSED_BUTTON: Color = Color::rgb(0.55, 0.75, 0.55);
-const TEXT_COLOR: Color = Color::BLACK;
+// Velocity buttons change by this much, in meters per second
+const VELOCITY_STEP: f32 = 0.5;
+// Acceleration buttons change by this much, in meters per second squared
+const ACCELERATION_STEP: f32 = 0.25;
+// Angular velocity buttons change by this much, in radians per second
+const ANGULAR_VELOCITY_STEP: f32 = 0.5;
 
-fn text(text: &str, asset_server: &AssetServer) -> TextBundle {
-    TextBundle {
-        style: Style {
-            margin: Rect::all(Val::Px(5.0)),
-            ..Default::default()
-        },
-        text: Text::with_section(
-            text,
-            TextStyle {
-                font: asset_server.load("fonts/JetBrainsMono-Bold.ttf"),
-                font_size: FONT_SIZE,
-                color: Color::GRAY,
-            },
-            TextAlignment::default(),
-        ),
-        ..TextBundle::default()
-    }
+// A label, the text that keeps it up to date and the buttons after it
+type ConfigRow = (&'static str, Option<ConfigText>, &'static [(&'static str, ConfigButtonAction)]);
+
+// The panel from the top
+const ROWS: [ConfigRow; 20] = [
+    ("Waypoint Number", None, &[("+", ConfigButtonAction::AddWaypoint), ("-", ConfigButtonAction::RemoveWaypoint)]),
+    ("Routine: ", Some(ConfigText::RoutineNumber), &[("+", ConfigButtonAction::IncrementPathIdx), ("-", ConfigButtonAction::DecrementPathIdx)]),
+    ("", None, &[("Add Path", ConfigButtonAction::AddPath)]),
+    ("", None, &[("Open", ConfigButtonAction::Open), ("Save", ConfigButtonAction::Save), ("Save As", ConfigButtonAction::SaveAs)]),
+    ("File: ", Some(ConfigText::File), &[]),
+    ("Paths", None, &[
+        ("Import", ConfigButtonAction::Import),
+        ("PathPlanner", ConfigButtonAction::ExportPathPlanner),
+        ("PathWeaver", ConfigButtonAction::ExportPathWeaver)
+    ]),
+    ("Trajectories", None, &[("JSON", ConfigButtonAction::ExportTrajectoryJson), ("CSV", ConfigButtonAction::ExportTrajectoryCsv)]),
+    ("Snap", None, &[
+        ("Grid", ConfigButtonAction::ToggleGridSnap),
+        ("Features", ConfigButtonAction::ToggleFeatureSnap),
+        ("15°", ConfigButtonAction::ToggleHeadingSnap)
+    ]),
+    ("Snapping: ", Some(ConfigText::Snap), &[]),
+    ("Grid: ", Some(ConfigText::GridSize), &[("+", ConfigButtonAction::CoarserGrid), ("-", ConfigButtonAction::FinerGrid)]),
+    ("Max speed: ", Some(ConfigText::MaxVelocity), &limit_buttons(PathLimit::MaxVelocity)),
+    ("Max accel: ", Some(ConfigText::MaxAcceleration), &limit_buttons(PathLimit::MaxAcceleration)),
+    ("Max turn: ", Some(ConfigText::MaxAngularVelocity), &limit_buttons(PathLimit::MaxAngularVelocity)),
+    ("Start: ", Some(ConfigText::StartVelocity), &limit_buttons(PathLimit::StartVelocity)),
+    ("End: ", Some(ConfigText::EndVelocity), &limit_buttons(PathLimit::EndVelocity)),
+    ("Waypoint max: ", Some(ConfigText::WaypointVelocity), &limit_buttons(PathLimit::WaypointVelocity)),
+    ("Zones", None, &[
+        ("Rect", ConfigButtonAction::DrawRectangleZone),
+        ("Polygon", ConfigButtonAction::DrawPolygonZone),
+        ("Delete", ConfigButtonAction::DeleteZone)
+    ]),
+    ("Zone: ", Some(ConfigText::Zone), &[]),
+    ("Zone max: ", Some(ConfigText::ZoneVelocity), &zone_limit_buttons(ZoneLimit::Velocity)),
+    ("Zone accel: ", Some(ConfigText::ZoneAcceleration), &zone_limit_buttons(ZoneLimit::Acceleration)),
+];
+
+const fn limit_buttons(limit: PathLimit) -> [(&'static str, ConfigButtonAction); 2] {
+    [("+", ConfigButtonAction::RaiseLimit(limit)), ("-", ConfigButtonAction::LowerLimit(limit))]
+}
+
+const fn zone_limit_buttons(limit: ZoneLimit) -> [(&'static str, ConfigButtonAction); 2] {
+    [("+", ConfigButtonAction::RaiseZoneLimit(limit)), ("-", ConfigButtonAction::LowerZoneLimit(limit))]
 }
 
 pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = ui::text_style(&asset_server, FONT_SIZE);
+
     commands.spawn_bundle(NodeBundle {
         color: UiColor(Color::NONE),
         ..Default::default()
     }).with_children(|parent| {
-        parent.spawn_bundle(NodeBundle {
-            color: UiColor(Color::NONE),
-            ..Default::default()
-        }).with_children(|parent_2| {
-            parent_2.spawn_bundle(text("Waypoint Number", &asset_server));
-            generate_button(parent_2, "+".to_string(), &asset_server, ConfigButton {
-                action: ConfigButtonAction::AddWaypoint
-            });
-            generate_button(parent_2, "-".to_string(), &asset_server, ConfigButton {
-                action: ConfigButtonAction::RemoveWaypoint
-            });
-        });
-        parent.spawn_bundle(NodeBundle {
-            color: UiColor(Color::NONE),
-            ..Default::default()
-        }).with_children(|parent_2| {
-            parent_2.spawn_bundle(text("Routine: ", &asset_server)).insert(ConfigText::RoutineNumber);
-            generate_button(parent_2, "+".to_string(), &asset_server, ConfigButton {
-                action: ConfigButtonAction::IncrementPathIdx
-            });
-            generate_button(parent_2, "-".to_string(), &asset_server, ConfigButton {
-                action: ConfigButtonAction::DecrementPathIdx
+        for (label, text, buttons) in ROWS {
+            parent.spawn_bundle(ui::row()).with_children(|row| {
+                match text {
+                    Some(t) => {
+                        row.spawn_bundle(ui::text(label, &text_style)).insert(t);
+                    }
+                    None if !label.is_empty() => {
+                        row.spawn_bundle(ui::text(label, &text_style));
+                    }
+                    None => {}
+                }
+                for (label, action) in buttons {
+                    ui::generate_button(row, label, &text_style, ConfigButton {
+                        action: *action
+                    });
+                }
             });
-        });
-        generate_button(parent, "Add Path".to_string(), &asset_server, ConfigButton {
-            action: ConfigButtonAction::AddPath
-        });
-    }).insert(ConfigRoot {
-
-    });
-}
-
-fn generate_button(parent: &mut ChildBuilder, text: String, asset_server: &AssetServer, component: ConfigButton) {
-    parent.spawn_bundle(ButtonBundle {
-        color: UiColor(NORMAL_BUTTON),
-        style: Style {
-            margin: Rect::all(Val::Px(5.0)),
-            ..Default::default()
-        },
-        ..Default::default()
-    }).with_children(|button_parent| {
-        button_parent.spawn_bundle(TextBundle {
-            style: Style {
-                margin: Rect::all(Val::Px(7.0)),
-                ..Default::default()
-            },
-            text: Text::with_section(
-                text,
-                TextStyle {
-                    font: asset_server.load("fonts/JetBrainsMono-Bold.ttf"),
-                    font_size: FONT_SIZE,
-                    color: TEXT_COLOR,
-                },
-                TextAlignment::default(),
-            ),
-            ..TextBundle::default()
-        });
-    }).insert(component);
+        }
+    }).insert(PanelRoot {
+        layout_rect: |l| &l.auto_cfg
+    }).insert(ConfigRoot);
 }
 
-pub fn root_updater(
-    mut query: Query<(&ConfigRoot, &mut Style)>,
-    layout_changed_event: Res<Events<LayoutChangedEvent>>
+pub fn config_text_updater(
+    mut query: Query<(&mut Text, &ConfigText)>,
+    list: Res<FieldWaypointList>,
+    file_path: Res<RoutineFilePath>,
+    snapping: Res<Snapping>,
+    cursor_state: Res<CursorState>,
+    (zones, zone_editor): (Res<ConstraintZones>, Res<ZoneEditor>)
 ) {
-    match layout_changed_event.get_reader().iter(&layout_changed_event).next_back() {
-        None => {}
-        Some(e) => {
-            let layout: &Layout = &e.0;
-
-            for i in query.iter_mut() {
-                let (_, mut style): (&ConfigRoot, Mut<Style>) = i;
-                *style = Style {
-                    size: Size::new(Val::Px(layout.auto_cfg.size.x), Val::Px(layout.auto_cfg.size.y - FONT_SIZE)),
-                    justify_content: JustifyContent::FlexStart,
-                    align_items: AlignItems::FlexStart,
-                    position_type: PositionType::Absolute,
-                    flex_direction: FlexDirection::ColumnReverse,
-                    position: Rect {
-                        left: Val::Px(layout.auto_cfg.pos.x + (layout.screen_size.x / 2.0)),
-                        bottom: Val::Px(layout.auto_cfg.pos.y + (layout.screen_size.y / 2.0)),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                };
-            }
-        }
-    };
-}
+    let constraints = &list.2[list.1];
+    let zone = zone_editor.selected.and_then(|idx| zones.0.get(idx));
 
-pub fn config_text_updater(mut query: Query<(&mut Text, &ConfigText)>, list: Res<FieldWaypointList>) {
     for i in query.iter_mut() {
         let (mut text, t): (Mut<Text>, &ConfigText) = i;
-        match t {
-            ConfigText::RoutineNumber => {
-                text.sections[0].value = "Routine: ".to_string() + &*list.1.to_string();
+        let value = match t {
+            ConfigText::RoutineNumber => "Routine: ".to_string() + &*list.1.to_string(),
+            ConfigText::File => {
+                let name = match file_path.0.as_ref().and_then(|p| p.file_name()) {
+                    Some(name) => name.to_string_lossy().to_string(),
+                    None => "unsaved".to_string()
+                };
+                "File: ".to_string() + &name
             }
-        }
+            ConfigText::Snap => {
+                let enabled: Vec<&str> = [(snapping.grid, "grid"), (snapping.features, "features"), (snapping.heading, "15°")]
+                    .iter()
+                    .filter(|(on, _)| *on)
+                    .map(|(_, name)| *name)
+                    .collect();
+                match enabled.is_empty() {
+                    true => "Snapping: off".to_string(),
+                    false => "Snapping: ".to_string() + &enabled.join(", ")
+                }
+            }
+            ConfigText::GridSize => format!("Grid: {} m", snapping.grid_size().get::<meter>()),
+            ConfigText::MaxVelocity => format!("Max speed: {:.1} m/s", constraints.config.max_velocity.get::<meter_per_second>()),
+            ConfigText::MaxAcceleration => format!("Max accel: {:.2} m/s²", constraints.config.max_acceleration.get::<meter_per_second_squared>()),
+            ConfigText::MaxAngularVelocity => format!("Max turn: {:.1} rad/s", constraints.config.max_angular_velocity.get::<radian_per_second>()),
+            ConfigText::StartVelocity => format!("Start: {:.1} m/s", constraints.start_velocity.get::<meter_per_second>()),
+            ConfigText::EndVelocity => format!("End: {:.1} m/s", constraints.end_velocity.get::<meter_per_second>()),
+            ConfigText::WaypointVelocity => match interior_selection(&cursor_state, &list) {
+                Some(idx) => match constraints.max_velocities[idx] {
+                    Some(v) => format!("Waypoint max: {:.1} m/s", v.get::<meter_per_second>()),
+                    None => "Waypoint max: none".to_string()
+                },
+                None => "Waypoint max: -".to_string()
+            },
+            ConfigText::Zone => match (cursor_state.tool, zone_editor.selected) {
+                (CursorTool::ZoneRectangle, _) => "Zone: drag out a rectangle".to_string(),
+                (CursorTool::ZonePolygon, _) => "Zone: click corners, right click to close".to_string(),
+                (CursorTool::Waypoints, Some(idx)) if idx < zones.0.len() => format!("Zone: {} of {}", idx + 1, zones.0.len()),
+                (CursorTool::Waypoints, _) => format!("Zone: none selected of {}", zones.0.len())
+            },
+            ConfigText::ZoneVelocity => match zone.map(|z| z.max_velocity) {
+                Some(Some(v)) => format!("Zone max: {:.1} m/s", v.get::<meter_per_second>()),
+                Some(None) => "Zone max: none".to_string(),
+                None => "Zone max: -".to_string()
+            },
+            ConfigText::ZoneAcceleration => match zone.map(|z| z.max_acceleration) {
+                Some(Some(a)) => format!("Zone accel: {:.2} m/s²", a.get::<meter_per_second_squared>()),
+                Some(None) => "Zone accel: none".to_string(),
+                None => "Zone accel: -".to_string()
+            }
+        };
+        ui::set_text_if_changed(&mut text, value);
     }
 }
 
+// Every panel button is handled here, actions that change the routines or zones return the edit that makes them undoable
 pub fn button_system(
-    mut interaction_query: Query<
-        (&Interaction, &mut UiColor, &Children, &ConfigButton),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut text_query: Query<&mut Text>,
-    mut waypoint_list: ResMut<FieldWaypointList>,
+    interaction_query: Query<(&Interaction, &ConfigButton), Changed<Interaction>>,
+    (routine_entities, trajectories): (Query<Entity, RoutineEntityFilter>, Query<(&TrajectoryID, &TimedTrajectory)>),
+    // Everything a routine file holds
+    (mut waypoint_list, mut zones): (ResMut<FieldWaypointList>, ResMut<ConstraintZones>),
+    (mut cursor_state, mut zone_editor, mut snapping): (ResMut<CursorState>, ResMut<ZoneEditor>, ResMut<Snapping>),
+    (mut file_path, field): (ResMut<RoutineFilePath>, Res<Field>),
+    mut history: ResMut<EditHistory>,
     mut commands: Commands
 ) {
-    for i in interaction_query.iter_mut() {
-        let (interaction, mut color, children, button): (&Interaction, Mut<UiColor>, &Children, &ConfigButton) = i;
-        let mut text = text_query.get_mut(children[0]).unwrap();
-        match *interaction {
-            Interaction::Clicked => {
-                *color = PRESSED_BUTTON.into();
-
-                match button.action {
-                    ConfigButtonAction::AddWaypoint => {
-                        let path_idx = waypoint_list.1;
-                        let l = waypoint_list.0[path_idx].last_mut().unwrap();
-                        *l = match l {
-                            Waypoint::Translation(t) => { Waypoint::Translation(*t) }
-                            Waypoint::Pose(p) => { Waypoint::Translation(p.translation) }
-                        };
-
-                        spawn_waypoint(
-                            Waypoint::Pose(FieldPose::new(FieldPosition::new(Length::new::<meter>(1.0), Length::new::<meter>(1.0)), Angle::ZERO)),
-                            &mut waypoint_list,
-                            &mut commands,
-                            path_idx
-                        )
-                    }
-                    ConfigButtonAction::RemoveWaypoint => {
-                        let path_idx = waypoint_list.1;
+    for i in interaction_query.iter() {
+        let (interaction, button): (&Interaction, &ConfigButton) = i;
 
-                        if waypoint_list.0[path_idx].len() <= 2 {
-                            return;
-                        }
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
 
-                        waypoint_list.0[path_idx].pop();
-
-                        let l = waypoint_list.0[path_idx].last_mut().unwrap();
-                        *l = match l {
-                            Waypoint::Translation(t) => {
-                                Waypoint::Pose(
-                                    FieldPose::new(*t, Angle::ZERO)
-                                )
-                            }
-                            Waypoint::Pose(p) => {
-                                Waypoint::Pose(*p)
-                            }
-                        };
-                    }
-                    ConfigButtonAction::IncrementPathIdx => {
-                        if waypoint_list.1 >= waypoint_list.0.len() - 1 {
-                            waypoint_list.1 = 0;
-                        } else {
-                            waypoint_list.1 += 1;
+        let edit = match button.action {
+            ConfigButtonAction::AddWaypoint => Some(add_waypoint(&waypoint_list)),
+            ConfigButtonAction::RemoveWaypoint => remove_waypoint(&waypoint_list),
+            ConfigButtonAction::IncrementPathIdx => {
+                waypoint_list.1 = (waypoint_list.1 + 1) % waypoint_list.0.len();
+                None
+            }
+            ConfigButtonAction::DecrementPathIdx => {
+                waypoint_list.1 = (waypoint_list.1 + waypoint_list.0.len() - 1) % waypoint_list.0.len();
+                None
+            }
+            ConfigButtonAction::AddPath => Some(add_path(&waypoint_list)),
+            ConfigButtonAction::Open => {
+                if let Some(path) = routine_file::pick_open_path(&file_path) {
+                    match routine_file::load(&path) {
+                        Ok(file) => {
+                            replace_routines(file.routines, file.constraints, &mut waypoint_list, &mut commands, routine_entities.iter());
+                            zones.0 = file.zones;
+                            history.clear();
+                            file_path.0 = Some(path);
                         }
+                        Err(e) => warn!("Could not open {}: {}", path.display(), e)
                     }
-                    ConfigButtonAction::DecrementPathIdx => {
-                        if waypoint_list.1 == 0 {
-                            waypoint_list.1 = waypoint_list.0.len() - 1;
-                        } else {
-                            waypoint_list.1 -= 1;
-                        }
+                }
+                None
+            }
+            ConfigButtonAction::Save | ConfigButtonAction::SaveAs => {
+                let path = match (button.action, &file_path.0) {
+                    (ConfigButtonAction::Save, Some(path)) => Some(path.clone()),
+                    _ => routine_file::pick_save_path(&file_path)
+                };
+                if let Some(path) = path {
+                    match routine_file::save(&path, &waypoint_list.0, &waypoint_list.2, &zones.0) {
+                        Ok(()) => file_path.0 = Some(path),
+                        Err(e) => warn!("Could not save {}: {}", path.display(), e)
                     }
-                    ConfigButtonAction::AddPath => {
-                        let path_idx = waypoint_list.0.len();
-                        let start_pos = match waypoint_list.0.last().unwrap().last().unwrap() {
-                            Waypoint::Translation(t) => { *t }
-                            Waypoint::Pose(p) => { p.translation }
-                        };
-                        waypoint_list.0.push(vec![]);
-                        spawn_waypoint(
-                            Waypoint::Pose(FieldPose::new(start_pos, Angle::ZERO)),
-                            &mut waypoint_list,
-                            &mut commands,
-                            path_idx
-                        );
-                        spawn_waypoint(
-                            Waypoint::Pose(FieldPose::new(FieldPosition::new(Length::new::<meter>(2.0), Length::new::<meter>(1.0)), Angle::ZERO)),
-                            &mut waypoint_list,
-                            &mut commands,
-                            path_idx
-                        );
-                        let default_shape = shapes::Circle::default();
-                        commands.spawn_bundle(GeometryBuilder::build_as(
-                            &default_shape,
-                            DrawMode::Stroke(StrokeMode::new(Color::WHITE, 2.0)),
-                            Transform::from_xyz(0.0, 0.0, FieldZ::AUTO_PATH.0)
-                        )).insert(Trajectory::default()).insert(TrajectoryID(path_idx));
-                        waypoint_list.1 = path_idx;
+                }
+                None
+            }
+            ConfigButtonAction::Import => {
+                let paths = routine_file::pick_import_paths(&file_path);
+
+                // Every file becomes a routine, chained in order like the routines of one auto
+                match paths.iter().map(|p| path_formats::import(p, &field)).collect::<Result<Vec<_>, _>>() {
+                    Ok(paths) if paths.is_empty() => {}
+                    Ok(paths) => {
+                        let (routines, constraints) = paths.into_iter().unzip();
+                        replace_routines(routines, constraints, &mut waypoint_list, &mut commands, routine_entities.iter());
+                        history.clear();
+                        // Save shouldn't overwrite the routine file with someone else's paths
+                        file_path.0 = None;
+                    }
+                    Err(e) => warn!("Could not import paths: {}", e)
+                }
+                None
+            }
+            ConfigButtonAction::ExportPathPlanner | ConfigButtonAction::ExportPathWeaver => {
+                if let Some(dir) = routine_file::pick_export_dir(&file_path) {
+                    let name = routine_file::export_name(&file_path);
+                    let result = match button.action {
+                        ConfigButtonAction::ExportPathPlanner => path_formats::export_pathplanner(&dir, &name, &waypoint_list),
+                        _ => path_formats::export_pathweaver(&dir, &name, &waypoint_list, &field)
+                    };
+                    match result {
+                        Ok(paths) => match paths.first().and_then(|p| p.parent()) {
+                            Some(out) => info!("Exported {} paths to {}", paths.len(), out.display()),
+                            None => warn!("No paths to export")
+                        },
+                        Err(e) => warn!("Could not export to {}: {}", dir.display(), e)
                     }
                 }
+                None
+            }
+            ConfigButtonAction::ExportTrajectoryJson | ConfigButtonAction::ExportTrajectoryCsv => {
+                export_trajectories(&trajectories, &file_path, button.action == ConfigButtonAction::ExportTrajectoryCsv);
+                None
+            }
+            ConfigButtonAction::ToggleGridSnap => {
+                snapping.grid = !snapping.grid;
+                None
+            }
+            ConfigButtonAction::ToggleFeatureSnap => {
+                snapping.features = !snapping.features;
+                None
             }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
+            ConfigButtonAction::ToggleHeadingSnap => {
+                snapping.heading = !snapping.heading;
+                None
             }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
+            ConfigButtonAction::CoarserGrid => {
+                snapping.coarser_grid();
+                None
             }
+            ConfigButtonAction::FinerGrid => {
+                snapping.finer_grid();
+                None
+            }
+            ConfigButtonAction::RaiseLimit(limit) => adjust_limit(limit, true, &waypoint_list, &cursor_state),
+            ConfigButtonAction::LowerLimit(limit) => adjust_limit(limit, false, &waypoint_list, &cursor_state),
+            ConfigButtonAction::DrawRectangleZone | ConfigButtonAction::DrawPolygonZone => {
+                zone_editor.cancel();
+                cursor_state.tool = match button.action {
+                    ConfigButtonAction::DrawRectangleZone => CursorTool::ZoneRectangle,
+                    _ => CursorTool::ZonePolygon
+                };
+                None
+            }
+            ConfigButtonAction::DeleteZone => match zone_editor.selected.filter(|idx| *idx < zones.0.len()) {
+                Some(idx) => {
+                    zone_editor.selected = None;
+                    Some(Edit::RemoveZone { idx, zone: zones.0[idx].clone() })
+                }
+                None => None
+            },
+            ConfigButtonAction::RaiseZoneLimit(limit) => adjust_zone_limit(limit, true, &waypoint_list, &zones, &zone_editor),
+            ConfigButtonAction::LowerZoneLimit(limit) => adjust_zone_limit(limit, false, &waypoint_list, &zones, &zone_editor),
+        };
+
+        if let Some(edit) = edit {
+            history.perform(edit, &mut waypoint_list, &mut zones, &mut commands);
+        }
+        // A new path is selected straight away
+        if button.action == ConfigButtonAction::AddPath {
+            waypoint_list.1 = waypoint_list.0.len() - 1;
+        }
+    }
+}
+
+// Appends a waypoint to the active path, the old end becomes an interior waypoint
+fn add_waypoint(list: &FieldWaypointList) -> Edit {
+    let path_idx = list.1;
+    let idx = list.0[path_idx].len() - 1;
+    let last = list.0[path_idx][idx];
+    let interior = match last {
+        Waypoint::Translation(t) => { Waypoint::Translation(t) }
+        Waypoint::Pose(p) => { Waypoint::Translation(p.translation) }
+    };
+
+    Edit::Group(vec![
+        Edit::Set { path_id: path_idx, idx, before: last, after: interior },
+        Edit::Insert {
+            path_id: path_idx,
+            idx: idx + 1,
+            max_velocity: None,
+            waypoint: Waypoint::Pose(FieldPose::new(FieldPosition::new(Length::new::<meter>(1.0), Length::new::<meter>(1.0)), Angle::ZERO))
+        },
+    ])
+}
+
+// Drops the active path's last waypoint, a path keeps at least its start and end
+fn remove_waypoint(list: &FieldWaypointList) -> Option<Edit> {
+    let path_idx = list.1;
+
+    if list.0[path_idx].len() <= 2 {
+        return None;
+    }
+
+    let idx = list.0[path_idx].len() - 2;
+    let removed = *list.0[path_idx].last().unwrap();
+    let max_velocity = list.2[path_idx].max_velocities[idx + 1];
+    let new_last = list.0[path_idx][idx];
+    let end = match new_last {
+        Waypoint::Translation(t) => {
+            Waypoint::Pose(
+                FieldPose::new(t, Angle::ZERO)
+            )
+        }
+        Waypoint::Pose(p) => {
+            Waypoint::Pose(p)
+        }
+    };
+
+    Some(Edit::Group(vec![
+        Edit::Remove { path_id: path_idx, idx: idx + 1, waypoint: removed, max_velocity },
+        Edit::Set { path_id: path_idx, idx, before: new_last, after: end },
+    ]))
+}
+
+fn add_path(list: &FieldWaypointList) -> Edit {
+    let path_idx = list.0.len();
+    let start_pos = match list.0.last().unwrap().last().unwrap() {
+        Waypoint::Translation(t) => { *t }
+        Waypoint::Pose(p) => { p.translation }
+    };
+    Edit::PushPath {
+        path_id: path_idx,
+        waypoints: vec![
+            Waypoint::Pose(FieldPose::new(start_pos, Angle::ZERO)),
+            Waypoint::Pose(FieldPose::new(FieldPosition::new(Length::new::<meter>(2.0), Length::new::<meter>(1.0)), Angle::ZERO)),
+        ],
+        // Picks up where the previous path leaves off, with the same robot limits
+        constraints: PathConstraints {
+            config: list.2[path_idx - 1].config,
+            start_velocity: list.2[path_idx - 1].end_velocity,
+            max_velocities: vec![None, None],
+            ..Default::default()
         }
     }
-}
\ No newline at end of file
+}
+
+fn export_trajectories(trajectories: &Query<(&TrajectoryID, &TimedTrajectory)>, file_path: &RoutineFilePath, csv: bool) {
+    if let Some(dir) = routine_file::pick_export_dir(file_path) {
+        let mut sorted: Vec<(usize, &TimedTrajectory)> = trajectories.iter().map(|(id, t)| (id.0, t)).collect();
+        sorted.sort_by_key(|(id, _)| *id);
+
+        match path_formats::export_trajectories(&dir, &routine_file::export_name(file_path), &sorted, csv) {
+            Ok(paths) => info!("Exported {} of {} trajectories to {}", paths.len(), sorted.len(), dir.display()),
+            Err(e) => warn!("Could not export to {}: {}", dir.display(), e)
+        }
+    }
+}
+
+// The selected waypoint's index if it is inside the active path, the ends use the start and end velocities instead
+fn interior_selection(cursor_state: &CursorState, list: &FieldWaypointList) -> Option<usize> {
+    match cursor_state.selected(list) {
+        Some((path_id, idx)) if idx > 0 && idx < list.0[path_id].len() - 1 => Some(idx),
+        _ => None
+    }
+}
+
+fn adjust_limit(limit: PathLimit, raise: bool, list: &FieldWaypointList, cursor_state: &CursorState) -> Option<Edit> {
+    let step = Velocity::new::<meter_per_second>(VELOCITY_STEP);
+    let acceleration_step = Acceleration::new::<meter_per_second_squared>(ACCELERATION_STEP);
+    let angular_velocity_step = AngularVelocity::new::<radian_per_second>(ANGULAR_VELOCITY_STEP);
+
+    let path_id = list.1;
+    let before = list.2[path_id].clone();
+    let mut after = before.clone();
+    let max_velocity = before.config.max_velocity;
+
+    // Limits never go below one step, a path the robot can't drive wouldn't generate
+    match (limit, raise) {
+        (PathLimit::MaxVelocity, true) => after.config.max_velocity += step,
+        (PathLimit::MaxVelocity, false) => after.config.max_velocity = (after.config.max_velocity - step).max(step),
+        (PathLimit::MaxAcceleration, true) => after.config.max_acceleration += acceleration_step,
+        (PathLimit::MaxAcceleration, false) => after.config.max_acceleration = (after.config.max_acceleration - acceleration_step).max(acceleration_step),
+        (PathLimit::MaxAngularVelocity, true) => after.config.max_angular_velocity += angular_velocity_step,
+        (PathLimit::MaxAngularVelocity, false) => {
+            after.config.max_angular_velocity = (after.config.max_angular_velocity - angular_velocity_step).max(angular_velocity_step)
+        }
+        (PathLimit::StartVelocity, true) => after.start_velocity = (after.start_velocity + step).min(max_velocity),
+        (PathLimit::StartVelocity, false) => after.start_velocity = (after.start_velocity - step).max(Velocity::ZERO),
+        (PathLimit::EndVelocity, true) => after.end_velocity = (after.end_velocity + step).min(max_velocity),
+        (PathLimit::EndVelocity, false) => after.end_velocity = (after.end_velocity - step).max(Velocity::ZERO),
+        (PathLimit::WaypointVelocity, _) => {
+            let idx = interior_selection(cursor_state, list)?;
+            // No limit sits above the fastest one, and the slowest still keeps the robot moving
+            after.max_velocities[idx] = match (raise, after.max_velocities[idx]) {
+                (true, Some(v)) if v + step < max_velocity => Some(v + step),
+                (true, _) => None,
+                (false, Some(v)) => Some((v - step).max(step)),
+                (false, None) => Some((max_velocity - step).max(step)),
+            };
+        }
+    }
+    // The ends can't be faster than the path allows, lowering the limit takes them down with it
+    after.start_velocity = after.start_velocity.min(after.config.max_velocity);
+    after.end_velocity = after.end_velocity.min(after.config.max_velocity);
+
+    match after != before {
+        true => Some(Edit::SetConstraints { path_id, before, after }),
+        false => None
+    }
+}
+
+// Zones apply to every routine, their undo steps don't switch the active one
+fn adjust_zone_limit(limit: ZoneLimit, raise: bool, list: &FieldWaypointList, zones: &ConstraintZones, zone_editor: &ZoneEditor) -> Option<Edit> {
+    let velocity_step = Velocity::new::<meter_per_second>(VELOCITY_STEP);
+    let acceleration_step = Acceleration::new::<meter_per_second_squared>(ACCELERATION_STEP);
+    // A zone limit above every path's own limit does nothing
+    let config = list.2.iter().fold(TrajectoryConfig {
+        max_velocity: Velocity::ZERO,
+        max_acceleration: Acceleration::ZERO,
+        ..Default::default()
+    }, |max, c| TrajectoryConfig {
+        max_velocity: max.max_velocity.max(c.config.max_velocity),
+        max_acceleration: max.max_acceleration.max(c.config.max_acceleration),
+        ..max
+    });
+
+    let (idx, before) = zone_editor.selected.and_then(|idx| zones.0.get(idx).map(|z| (idx, z.clone())))?;
+    let mut after = before.clone();
+
+    // Like waypoint limits, no limit sits above the paths' own and the smallest step is the lowest
+    match (limit, raise) {
+        (ZoneLimit::Velocity, true) => after.max_velocity = match after.max_velocity {
+            Some(v) if v + velocity_step < config.max_velocity => Some(v + velocity_step),
+            _ => None
+        },
+        (ZoneLimit::Velocity, false) => after.max_velocity = match after.max_velocity {
+            Some(v) => Some((v - velocity_step).max(velocity_step)),
+            None => Some((config.max_velocity - velocity_step).max(velocity_step))
+        },
+        (ZoneLimit::Acceleration, true) => after.max_acceleration = match after.max_acceleration {
+            Some(a) if a + acceleration_step < config.max_acceleration => Some(a + acceleration_step),
+            _ => None
+        },
+        (ZoneLimit::Acceleration, false) => after.max_acceleration = match after.max_acceleration {
+            Some(a) => Some((a - acceleration_step).max(acceleration_step)),
+            None => Some((config.max_acceleration - acceleration_step).max(acceleration_step))
+        },
+    }
+
+    match after != before {
+        true => Some(Edit::SetZone { idx, before, after }),
+        false => None
+    }
+}
@@ -0,0 +1,464 @@
+use serde::{Deserialize, Serialize};
+use uom::si::acceleration::meter_per_second_squared;
+use uom::si::angle::radian;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::curvature::radian_per_meter;
+use uom::si::f32::*;
+use uom::si::length::meter;
+use uom::si::time::second;
+use uom::si::velocity::meter_per_second;
+
+use crate::auto_pathing::trajectory::{TimedTrajectory, Trajectory, TrajectoryState};
+use crate::auto_pathing::zones::ConstraintZone;
+use crate::field::{wrap_angle, FieldPose, FieldPosition};
+
+// Maximum distance between two parameterized points along a spline
+const MAX_STEP_M: f32 = 0.02;
+// A point's velocity cap applies this close to it, the remote generator uses the same region
+pub const POINT_CONSTRAINT_RADIUS_M: f32 = 0.1;
+
+// Limits for one path, set per path in the config panel and sent with every trajectory
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectoryConfig {
+    pub max_velocity: Velocity,
+    pub max_acceleration: Acceleration,
+    // How fast the robot may turn to its heading targets
+    pub max_angular_velocity: AngularVelocity,
+}
+
+impl Default for TrajectoryConfig {
+    fn default() -> Self {
+        Self {
+            max_velocity: Velocity::new::<meter_per_second>(5.0),
+            max_acceleration: Acceleration::new::<meter_per_second_squared>(1.0),
+            max_angular_velocity: AngularVelocity::new::<radian_per_second>(3.0),
+        }
+    }
+}
+
+// Cubic hermite spline segment, one control vector (position, derivative) per axis and end
+#[derive(Debug, Copy, Clone)]
+struct CubicHermiteSpline {
+    x: [f32; 4],
+    y: [f32; 4],
+}
+
+#[derive(Debug, Copy, Clone)]
+struct SplinePoint {
+    x: f32,
+    y: f32,
+    heading: f32,
+    curvature: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct TimedPoint {
+    time: f32,
+    point: SplinePoint,
+    // Where the robot faces, unlike the point's heading which is the direction of travel
+    rotation: f32,
+    velocity: f32,
+    acceleration: f32,
+}
+
+impl CubicHermiteSpline {
+    fn new(x_initial: [f32; 2], x_final: [f32; 2], y_initial: [f32; 2], y_final: [f32; 2]) -> Self {
+        Self {
+            x: [x_initial[0], x_initial[1], x_final[0], x_final[1]],
+            y: [y_initial[0], y_initial[1], y_final[0], y_final[1]],
+        }
+    }
+
+    // Returns (value, first derivative, second derivative) of one axis at t
+    fn eval_axis(c: &[f32; 4], t: f32) -> (f32, f32, f32) {
+        let (p0, m0, p1, m1) = (c[0], c[1], c[2], c[3]);
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        let value = (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+            + (t3 - 2.0 * t2 + t) * m0
+            + (-2.0 * t3 + 3.0 * t2) * p1
+            + (t3 - t2) * m1;
+        let d1 = (6.0 * t2 - 6.0 * t) * p0
+            + (3.0 * t2 - 4.0 * t + 1.0) * m0
+            + (-6.0 * t2 + 6.0 * t) * p1
+            + (3.0 * t2 - 2.0 * t) * m1;
+        let d2 = (12.0 * t - 6.0) * p0
+            + (6.0 * t - 4.0) * m0
+            + (-12.0 * t + 6.0) * p1
+            + (6.0 * t - 2.0) * m1;
+
+        (value, d1, d2)
+    }
+
+    fn point(&self, t: f32) -> SplinePoint {
+        let (x, dx, ddx) = Self::eval_axis(&self.x, t);
+        let (y, dy, ddy) = Self::eval_axis(&self.y, t);
+
+        let speed_sq = dx * dx + dy * dy;
+        let curvature = if speed_sq > f32::EPSILON {
+            (dx * ddy - dy * ddx) / speed_sq.powf(1.5)
+        } else {
+            0.0
+        };
+
+        SplinePoint {
+            x,
+            y,
+            heading: dy.atan2(dx),
+            curvature,
+        }
+    }
+
+    fn chord_length(&self) -> f32 {
+        let (x0, y0) = (self.x[0], self.y[0]);
+        let (x1, y1) = (self.x[2], self.y[2]);
+        ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt()
+    }
+}
+
+fn control_vector(pose: &FieldPose, scalar: f32) -> ([f32; 2], [f32; 2]) {
+    let theta = pose.rotation.get::<radian>();
+    (
+        [pose.translation.x.get::<meter>(), scalar * theta.cos()],
+        [pose.translation.y.get::<meter>(), scalar * theta.sin()],
+    )
+}
+
+fn to_xy(p: &FieldPosition) -> (f32, f32) {
+    (p.x.get::<meter>(), p.y.get::<meter>())
+}
+
+// Solves a tridiagonal system where a, b and c are the below, main and above diagonals
+fn thomas_algorithm(a: &[f32], b: &[f32], c: &[f32], d: &[f32]) -> Vec<f32> {
+    let n = d.len();
+    let mut c_star = vec![0.0; n];
+    let mut d_star = vec![0.0; n];
+
+    c_star[0] = c[0] / b[0];
+    d_star[0] = d[0] / b[0];
+
+    for i in 1..n {
+        let m = 1.0 / (b[i] - a[i] * c_star[i - 1]);
+        c_star[i] = c[i] * m;
+        d_star[i] = (d[i] - a[i] * d_star[i - 1]) * m;
+    }
+
+    let mut solution = vec![0.0; n];
+    solution[n - 1] = d_star[n - 1];
+    for i in (0..n - 1).rev() {
+        solution[i] = d_star[i] - c_star[i] * solution[i + 1];
+    }
+
+    solution
+}
+
+// Clamped cubic splines through the interior points, same construction as wpimath's SplineHelper
+fn build_splines(start: &FieldPose, points: &[FieldPosition], end: &FieldPose) -> Vec<CubicHermiteSpline> {
+    let (first_interior, last_interior) = match (points.first(), points.last()) {
+        (Some(f), Some(l)) => (*f, *l),
+        _ => (end.translation, start.translation),
+    };
+
+    let (x_initial, y_initial) = control_vector(start, 1.2 * start.translation.dist(&first_interior).get::<meter>());
+    let (x_final, y_final) = control_vector(end, 1.2 * last_interior.dist(&end.translation).get::<meter>());
+
+    match points.len() {
+        0 => vec![CubicHermiteSpline::new(x_initial, x_final, y_initial, y_final)],
+        1 => {
+            let (mx, my) = to_xy(&points[0]);
+            let x_deriv = (3.0 * (x_final[0] - x_initial[0]) - x_final[1] - x_initial[1]) / 4.0;
+            let y_deriv = (3.0 * (y_final[0] - y_initial[0]) - y_final[1] - y_initial[1]) / 4.0;
+
+            vec![
+                CubicHermiteSpline::new(x_initial, [mx, x_deriv], y_initial, [my, y_deriv]),
+                CubicHermiteSpline::new([mx, x_deriv], x_final, [my, y_deriv], y_final),
+            ]
+        }
+        n => {
+            let mut waypoints: Vec<(f32, f32)> = Vec::with_capacity(n + 2);
+            waypoints.push((x_initial[0], y_initial[0]));
+            waypoints.extend(points.iter().map(to_xy));
+            waypoints.push((x_final[0], y_final[0]));
+
+            let mut a = vec![1.0; n];
+            a[0] = 0.0;
+            let b = vec![4.0; n];
+            let mut c = vec![1.0; n];
+            c[n - 1] = 0.0;
+
+            let mut dx = Vec::with_capacity(n);
+            let mut dy = Vec::with_capacity(n);
+            dx.push(3.0 * (waypoints[2].0 - waypoints[0].0) - x_initial[1]);
+            dy.push(3.0 * (waypoints[2].1 - waypoints[0].1) - y_initial[1]);
+            for i in 1..n - 1 {
+                dx.push(3.0 * (waypoints[i + 2].0 - waypoints[i].0));
+                dy.push(3.0 * (waypoints[i + 2].1 - waypoints[i].1));
+            }
+            dx.push(3.0 * (waypoints[n + 1].0 - waypoints[n - 1].0) - x_final[1]);
+            dy.push(3.0 * (waypoints[n + 1].1 - waypoints[n - 1].1) - y_final[1]);
+
+            let mut fx = vec![x_initial[1]];
+            fx.extend(thomas_algorithm(&a, &b, &c, &dx));
+            fx.push(x_final[1]);
+
+            let mut fy = vec![y_initial[1]];
+            fy.extend(thomas_algorithm(&a, &b, &c, &dy));
+            fy.push(y_final[1]);
+
+            (0..waypoints.len() - 1).map(|i| {
+                CubicHermiteSpline::new(
+                    [waypoints[i].0, fx[i]],
+                    [waypoints[i + 1].0, fx[i + 1]],
+                    [waypoints[i].1, fy[i]],
+                    [waypoints[i + 1].1, fy[i + 1]],
+                )
+            }).collect()
+        }
+    }
+}
+
+// Also returns the index of the last point of each spline, which is where the next waypoint is
+fn parameterize_splines(splines: &[CubicHermiteSpline]) -> (Vec<SplinePoint>, Vec<usize>) {
+    let mut points = vec![splines[0].point(0.0)];
+    let mut ends = Vec::with_capacity(splines.len());
+
+    for spline in splines {
+        // The chord underestimates the arc length, so oversample a bit
+        let steps = ((2.0 * spline.chord_length() / MAX_STEP_M).ceil() as usize).max(8);
+        for i in 1..=steps {
+            points.push(spline.point(i as f32 / steps as f32));
+        }
+        ends.push(points.len() - 1);
+    }
+
+    (points, ends)
+}
+
+// Robot rotation at every point and how fast it turns per meter. Rotation is interpolated by distance between the
+// start pose, the interior rotation targets and the end pose, whatever way the path itself is heading.
+fn rotation_profile(ds: &[f32], spline_ends: &[usize], trajectory: &Trajectory) -> Vec<(f32, f32)> {
+    let mut distances = vec![0.0];
+    for d in ds {
+        distances.push(distances.last().unwrap() + d);
+    }
+
+    let mut targets = vec![(0, trajectory.start.rotation.get::<radian>())];
+    for (end, rotation) in spline_ends.iter().zip(trajectory.rotation_targets.iter()) {
+        if let Some(rotation) = rotation {
+            targets.push((*end, rotation.get::<radian>()));
+        }
+    }
+    targets.push((distances.len() - 1, trajectory.end.rotation.get::<radian>()));
+
+    // Each target is reached the short way round from the one before
+    for i in 1..targets.len() {
+        targets[i].1 = targets[i - 1].1 + wrap_angle(targets[i].1 - targets[i - 1].1);
+    }
+
+    let mut profile = Vec::with_capacity(distances.len());
+    for w in targets.windows(2) {
+        let ((from, from_rotation), (to, to_rotation)) = (w[0], w[1]);
+        let length = distances[to] - distances[from];
+        let rate = if length > f32::EPSILON { (to_rotation - from_rotation) / length } else { 0.0 };
+
+        // Every segment after the first starts where the last one ended
+        let first = if profile.is_empty() { from } else { from + 1 };
+        for distance in &distances[first..=to] {
+            profile.push((from_rotation + rate * (distance - distances[from]), rate.abs()));
+        }
+    }
+
+    profile
+}
+
+// Forward/backward pass trapezoidal profile over the arc length of the path
+fn time_parameterize(points: Vec<SplinePoint>, spline_ends: &[usize], trajectory: &Trajectory) -> Vec<TimedPoint> {
+    let max_v = trajectory.config.max_velocity.get::<meter_per_second>();
+    let max_a = trajectory.config.max_acceleration.get::<meter_per_second_squared>();
+    let max_omega = trajectory.config.max_angular_velocity.get::<radian_per_second>();
+
+    let ds: Vec<f32> = points.windows(2).map(|w| {
+        ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt()
+    }).collect();
+
+    let rotations = rotation_profile(&ds, spline_ends, trajectory);
+
+    let caps: Vec<(f32, f32, f32)> = trajectory.points.iter()
+        .zip(trajectory.point_max_velocities.iter())
+        .filter_map(|(p, v)| v.map(|v| (p.x.get::<meter>(), p.y.get::<meter>(), v.get::<meter_per_second>())))
+        .collect();
+
+    // Zones each point is inside of
+    let zones: Vec<Vec<&ConstraintZone>> = points.iter().map(|p| {
+        let position = FieldPosition::new(Length::new::<meter>(p.x), Length::new::<meter>(p.y));
+        trajectory.zones.iter().filter(|z| z.shape.contains(&position)).collect()
+    }).collect();
+
+    let mut velocities: Vec<f32> = points.iter().zip(zones.iter()).zip(rotations.iter()).map(|((p, zones), (_, rate))| {
+        // Turning at the max angular velocity only covers so much ground per radian
+        let v = if *rate > f32::EPSILON { max_v.min(max_omega / rate) } else { max_v };
+        let v = caps.iter()
+            .filter(|(x, y, _)| (p.x - x).hypot(p.y - y) <= POINT_CONSTRAINT_RADIUS_M)
+            .fold(v, |v, (_, _, cap)| v.min(*cap));
+        zones.iter()
+            .filter_map(|z| z.max_velocity)
+            .fold(v, |v, cap| v.min(cap.get::<meter_per_second>()))
+    }).collect();
+
+    // A step between two points is limited by the zones at both of its ends
+    let accelerations: Vec<f32> = zones.iter().map(|zones| {
+        zones.iter()
+            .filter_map(|z| z.max_acceleration)
+            .fold(max_a, |a, cap| a.min(cap.get::<meter_per_second_squared>()))
+    }).collect();
+    let step_a: Vec<f32> = accelerations.windows(2).map(|w| w[0].min(w[1])).collect();
+
+    velocities[0] = velocities[0].min(trajectory.start_velocity.get::<meter_per_second>());
+    for i in 1..points.len() {
+        velocities[i] = velocities[i].min((velocities[i - 1].powi(2) + 2.0 * step_a[i - 1] * ds[i - 1]).sqrt());
+    }
+
+    // An end velocity the path is too short to reach is lowered rather than failing
+    let last = points.len() - 1;
+    velocities[last] = velocities[last].min(trajectory.end_velocity.get::<meter_per_second>());
+    for i in (0..last).rev() {
+        velocities[i] = velocities[i].min((velocities[i + 1].powi(2) + 2.0 * step_a[i] * ds[i]).sqrt());
+    }
+
+    let mut timed: Vec<TimedPoint> = Vec::with_capacity(points.len());
+    let mut time = 0.0;
+
+    for (i, point) in points.into_iter().enumerate() {
+        if i > 0 {
+            let prev = timed.last_mut().unwrap();
+            let avg = (prev.velocity + velocities[i]) / 2.0;
+            let dt = if avg > f32::EPSILON { ds[i - 1] / avg } else { 0.0 };
+            if dt > 0.0 {
+                prev.acceleration = (velocities[i] - prev.velocity) / dt;
+            }
+            time += dt;
+        }
+
+        timed.push(TimedPoint {
+            time,
+            point,
+            rotation: rotations[i].0,
+            velocity: velocities[i],
+            acceleration: 0.0,
+        });
+    }
+
+    timed
+}
+
+impl From<TimedPoint> for TrajectoryState {
+    fn from(p: TimedPoint) -> Self {
+        TrajectoryState {
+            time: Time::new::<second>(p.time),
+            pose: FieldPose::new(
+                FieldPosition::new(Length::new::<meter>(p.point.x), Length::new::<meter>(p.point.y)),
+                Angle::new::<radian>(p.point.heading)
+            ),
+            holonomic_rotation: Angle::new::<radian>(wrap_angle(p.rotation)),
+            velocity: Velocity::new::<meter_per_second>(p.velocity),
+            acceleration: Acceleration::new::<meter_per_second_squared>(p.acceleration),
+            curvature: Curvature::new::<radian_per_meter>(p.point.curvature),
+        }
+    }
+}
+
+// Position and derivative along x and y at every knot of the splines generate() follows, per unit of spline parameter
+pub fn control_vectors(trajectory: &Trajectory) -> Vec<([f32; 2], [f32; 2])> {
+    let splines = build_splines(&trajectory.start, &trajectory.points, &trajectory.end);
+
+    let mut vectors = vec![([splines[0].x[0], splines[0].x[1]], [splines[0].y[0], splines[0].y[1]])];
+    vectors.extend(splines.iter().map(|s| ([s.x[2], s.x[3]], [s.y[2], s.y[3]])));
+    vectors
+}
+
+pub fn generate(trajectory: &Trajectory) -> Option<TimedTrajectory> {
+    let splines = build_splines(&trajectory.start, &trajectory.points, &trajectory.end);
+
+    if splines.iter().any(|s| s.x.iter().chain(s.y.iter()).any(|v| !v.is_finite())) {
+        return None;
+    }
+
+    let (points, spline_ends) = parameterize_splines(&splines);
+    let points = time_parameterize(points, &spline_ends, trajectory);
+
+    if !matches!(points.last(), Some(p) if p.time > 0.0) {
+        return None;
+    }
+
+    Some(TimedTrajectory(points.into_iter().map(|p| p.into()).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auto_pathing::zones::ZoneShape;
+
+    fn position(x: f32, y: f32) -> FieldPosition {
+        FieldPosition::new(Length::new::<meter>(x), Length::new::<meter>(y))
+    }
+
+    fn trajectory() -> Trajectory {
+        Trajectory {
+            start: FieldPose::new(position(0.0, 0.0), Angle::new::<radian>(0.0)),
+            points: vec![position(2.0, 1.0), position(4.0, 0.0)],
+            end: FieldPose::new(position(6.0, 1.0), Angle::new::<radian>(0.0)),
+            point_max_velocities: vec![None, None],
+            rotation_targets: vec![None, None],
+            config: TrajectoryConfig {
+                max_velocity: Velocity::new::<meter_per_second>(2.0),
+                max_acceleration: Acceleration::new::<meter_per_second_squared>(1.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn passes_through_waypoints() {
+        let t = trajectory();
+        let states = generate(&t).unwrap().0;
+
+        let waypoints = [t.start.translation].into_iter().chain(t.points.iter().copied()).chain([t.end.translation]);
+        for w in waypoints {
+            let closest = states.iter().map(|s| s.pose.translation.dist(&w).get::<meter>()).fold(f32::MAX, f32::min);
+            assert!(closest < 1e-3, "no state at {:?}, closest is {} m away", w, closest);
+        }
+    }
+
+    #[test]
+    fn respects_config_limits() {
+        let t = trajectory();
+        let states = generate(&t).unwrap().0;
+
+        for s in &states {
+            assert!(s.velocity.get::<meter_per_second>() <= 2.0 + 1e-4, "{:?}", s);
+            assert!(s.acceleration.get::<meter_per_second_squared>().abs() <= 1.0 + 1e-3, "{:?}", s);
+        }
+        assert!(states.iter().any(|s| s.velocity.get::<meter_per_second>() > 1.9));
+    }
+
+    #[test]
+    fn respects_zone_limits() {
+        let mut t = trajectory();
+        let shape = ZoneShape::Rectangle { min: position(1.5, -1.0), max: position(2.5, 2.0) };
+        t.zones = vec![ConstraintZone {
+            shape: shape.clone(),
+            max_velocity: Some(Velocity::new::<meter_per_second>(0.5)),
+            max_acceleration: Some(Acceleration::new::<meter_per_second_squared>(0.25)),
+        }];
+        let states = generate(&t).unwrap().0;
+
+        let inside: Vec<&TrajectoryState> = states.iter().filter(|s| shape.contains(&s.pose.translation)).collect();
+        assert!(!inside.is_empty());
+        for s in inside {
+            assert!(s.velocity.get::<meter_per_second>() <= 0.5 + 1e-4, "{:?}", s);
+            assert!(s.acceleration.get::<meter_per_second_squared>().abs() <= 0.25 + 1e-3, "{:?}", s);
+        }
+    }
+}
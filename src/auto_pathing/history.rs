@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use uom::si::f32::Velocity;
+
+use crate::auto_pathing::trajectory::spawn_trajectory;
+use crate::auto_pathing::waypoints::{FieldWaypointList, insert_waypoint, PathConstraints, spawn_waypoint, Waypoint};
+use crate::auto_pathing::zones::{ConstraintZone, ConstraintZones};
+
+// Undo steps kept before the oldest is dropped
+const MAX_HISTORY: usize = 200;
+
+// A reversible change to FieldWaypointList or ConstraintZones, entities for removed waypoints, paths and zones are
+// despawned by their updaters. Paths are only pushed and popped at the end of the list, waypoints and zones can go
+// anywhere in theirs.
+#[derive(Debug, Clone)]
+pub enum Edit {
+    // One waypoint replaced in place, moves and rotations
+    Set { path_id: usize, idx: usize, before: Waypoint, after: Waypoint },
+    Insert { path_id: usize, idx: usize, waypoint: Waypoint, max_velocity: Option<Velocity> },
+    Remove { path_id: usize, idx: usize, waypoint: Waypoint, max_velocity: Option<Velocity> },
+    PushPath { path_id: usize, waypoints: Vec<Waypoint>, constraints: PathConstraints },
+    PopPath { path_id: usize, waypoints: Vec<Waypoint>, constraints: PathConstraints },
+    SetConstraints { path_id: usize, before: PathConstraints, after: PathConstraints },
+    AddZone { idx: usize, zone: ConstraintZone },
+    RemoveZone { idx: usize, zone: ConstraintZone },
+    SetZone { idx: usize, before: ConstraintZone, after: ConstraintZone },
+    // Applied in order and reverted in reverse
+    Group(Vec<Edit>),
+}
+
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl Edit {
+    fn path_id(&self) -> Option<usize> {
+        match self {
+            Edit::Set { path_id, .. }
+            | Edit::Insert { path_id, .. }
+            | Edit::Remove { path_id, .. }
+            | Edit::PushPath { path_id, .. }
+            | Edit::PopPath { path_id, .. }
+            | Edit::SetConstraints { path_id, .. } => Some(*path_id),
+            // Zones apply to every routine
+            Edit::AddZone { .. } | Edit::RemoveZone { .. } | Edit::SetZone { .. } => None,
+            Edit::Group(edits) => edits.first().and_then(|e| e.path_id()),
+        }
+    }
+
+    fn inverse(&self) -> Edit {
+        match self {
+            Edit::Set { path_id, idx, before, after } => Edit::Set { path_id: *path_id, idx: *idx, before: *after, after: *before },
+            Edit::Insert { path_id, idx, waypoint, max_velocity } => Edit::Remove { path_id: *path_id, idx: *idx, waypoint: *waypoint, max_velocity: *max_velocity },
+            Edit::Remove { path_id, idx, waypoint, max_velocity } => Edit::Insert { path_id: *path_id, idx: *idx, waypoint: *waypoint, max_velocity: *max_velocity },
+            Edit::PushPath { path_id, waypoints, constraints } => Edit::PopPath { path_id: *path_id, waypoints: waypoints.clone(), constraints: constraints.clone() },
+            Edit::PopPath { path_id, waypoints, constraints } => Edit::PushPath { path_id: *path_id, waypoints: waypoints.clone(), constraints: constraints.clone() },
+            Edit::SetConstraints { path_id, before, after } => Edit::SetConstraints { path_id: *path_id, before: after.clone(), after: before.clone() },
+            Edit::AddZone { idx, zone } => Edit::RemoveZone { idx: *idx, zone: zone.clone() },
+            Edit::RemoveZone { idx, zone } => Edit::AddZone { idx: *idx, zone: zone.clone() },
+            Edit::SetZone { idx, before, after } => Edit::SetZone { idx: *idx, before: after.clone(), after: before.clone() },
+            Edit::Group(edits) => Edit::Group(edits.iter().rev().map(|e| e.inverse()).collect()),
+        }
+    }
+
+    pub fn apply(&self, list: &mut FieldWaypointList, zones: &mut ConstraintZones, commands: &mut Commands) {
+        match self {
+            Edit::Set { path_id, idx, after, .. } => list.0[*path_id][*idx] = *after,
+            Edit::Insert { path_id, idx, waypoint, max_velocity } => {
+                insert_waypoint(*waypoint, *idx, list, commands, *path_id);
+                list.2[*path_id].max_velocities[*idx] = *max_velocity;
+            }
+            Edit::Remove { path_id, idx, .. } => {
+                list.0[*path_id].remove(*idx);
+                list.2[*path_id].max_velocities.remove(*idx);
+            }
+            Edit::PushPath { path_id, waypoints, constraints } => {
+                for waypoint in waypoints {
+                    spawn_waypoint(*waypoint, list, commands, *path_id);
+                }
+                list.2[*path_id] = constraints.clone();
+                spawn_trajectory(commands, *path_id);
+            }
+            Edit::PopPath { .. } => {
+                list.0.pop();
+                list.2.pop();
+            }
+            Edit::SetConstraints { path_id, after, .. } => list.2[*path_id] = after.clone(),
+            Edit::AddZone { idx, zone } => zones.0.insert(*idx, zone.clone()),
+            Edit::RemoveZone { idx, .. } => {
+                zones.0.remove(*idx);
+            }
+            Edit::SetZone { idx, after, .. } => zones.0[*idx] = after.clone(),
+            Edit::Group(edits) => {
+                for edit in edits {
+                    edit.apply(list, zones, commands);
+                }
+            }
+        }
+    }
+
+    fn revert(&self, list: &mut FieldWaypointList, zones: &mut ConstraintZones, commands: &mut Commands) {
+        self.inverse().apply(list, zones, commands);
+    }
+}
+
+impl EditHistory {
+    // Applies an edit and makes it undoable
+    pub fn perform(&mut self, edit: Edit, list: &mut FieldWaypointList, zones: &mut ConstraintZones, commands: &mut Commands) {
+        edit.apply(list, zones, commands);
+        self.record(edit);
+    }
+
+    // Makes an edit that was already applied undoable, e.g. a finished drag
+    pub fn record(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, list: &mut FieldWaypointList, zones: &mut ConstraintZones, commands: &mut Commands) {
+        if let Some(edit) = self.undo.pop() {
+            edit.revert(list, zones, commands);
+            focus(&edit, list);
+            self.redo.push(edit);
+        }
+    }
+
+    pub fn redo(&mut self, list: &mut FieldWaypointList, zones: &mut ConstraintZones, commands: &mut Commands) {
+        if let Some(edit) = self.redo.pop() {
+            edit.apply(list, zones, commands);
+            focus(&edit, list);
+            self.undo.push(edit);
+        }
+    }
+}
+
+// Shows the routine an undo or redo touched, which also lets path continuity carry it over to the neighbours
+fn focus(edit: &Edit, list: &mut FieldWaypointList) {
+    if let Some(path_id) = edit.path_id() {
+        list.1 = path_id.min(list.0.len() - 1);
+    }
+}
+
+// Ctrl+Z undoes, Ctrl+Shift+Z or Ctrl+Y redoes
+pub fn history_keyboard_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut history: ResMut<EditHistory>,
+    (mut list, mut zones): (ResMut<FieldWaypointList>, ResMut<ConstraintZones>),
+    mut commands: Commands
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keyboard_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if !ctrl {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Z) && !shift {
+        history.undo(&mut list, &mut zones, &mut commands);
+    } else if (keyboard_input.just_pressed(KeyCode::Z) && shift) || keyboard_input.just_pressed(KeyCode::Y) {
+        history.redo(&mut list, &mut zones, &mut commands);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+    use uom::si::f32::Length;
+    use uom::si::length::meter;
+    use uom::si::velocity::meter_per_second;
+
+    use super::*;
+    use crate::auto_pathing::zones::ZoneShape;
+    use crate::field::FieldPosition;
+
+    fn waypoint(x: f32) -> Waypoint {
+        Waypoint::Translation(FieldPosition::new(Length::new::<meter>(x), Length::new::<meter>(0.0)))
+    }
+
+    fn zone(max_velocity: f32) -> ConstraintZone {
+        ConstraintZone {
+            shape: ZoneShape::Rectangle {
+                min: FieldPosition::new(Length::new::<meter>(0.0), Length::new::<meter>(0.0)),
+                max: FieldPosition::new(Length::new::<meter>(1.0), Length::new::<meter>(1.0)),
+            },
+            max_velocity: Some(Velocity::new::<meter_per_second>(max_velocity)),
+            max_acceleration: None,
+        }
+    }
+
+    fn list() -> FieldWaypointList {
+        FieldWaypointList(
+            vec![vec![waypoint(0.0), waypoint(1.0), waypoint(2.0)]],
+            0,
+            vec![PathConstraints { max_velocities: vec![None; 3], ..Default::default() }]
+        )
+    }
+
+    type Snapshot = (Vec<Vec<Waypoint>>, Vec<PathConstraints>, Vec<ConstraintZone>);
+
+    fn snapshot(list: &FieldWaypointList, zones: &ConstraintZones) -> Snapshot {
+        (list.0.clone(), list.2.clone(), zones.0.clone())
+    }
+
+    #[test]
+    fn inverse_restores_state() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        let velocity = Some(Velocity::new::<meter_per_second>(1.0));
+        let edits = vec![
+            Edit::Set { path_id: 0, idx: 1, before: waypoint(1.0), after: waypoint(1.5) },
+            Edit::Insert { path_id: 0, idx: 1, waypoint: waypoint(0.5), max_velocity: velocity },
+            Edit::Remove { path_id: 0, idx: 1, waypoint: waypoint(1.0), max_velocity: None },
+            Edit::PushPath { path_id: 1, waypoints: vec![waypoint(2.0), waypoint(3.0)], constraints: PathConstraints {
+                max_velocities: vec![None, velocity],
+                ..Default::default()
+            } },
+            Edit::SetConstraints { path_id: 0, before: list().2[0].clone(), after: PathConstraints {
+                start_velocity: Velocity::new::<meter_per_second>(1.0),
+                max_velocities: vec![None; 3],
+                ..Default::default()
+            } },
+            Edit::AddZone { idx: 0, zone: zone(1.0) },
+            Edit::Group(vec![
+                Edit::Insert { path_id: 0, idx: 3, waypoint: waypoint(3.0), max_velocity: None },
+                Edit::Set { path_id: 0, idx: 3, before: waypoint(3.0), after: waypoint(4.0) },
+            ]),
+        ];
+
+        for edit in edits {
+            let mut list = list();
+            let mut zones = ConstraintZones::default();
+            let before = snapshot(&list, &zones);
+
+            edit.apply(&mut list, &mut zones, &mut commands);
+            assert_ne!(snapshot(&list, &zones), before, "{:?} changed nothing", edit);
+            edit.inverse().apply(&mut list, &mut zones, &mut commands);
+            assert_eq!(snapshot(&list, &zones), before, "{:?} wasn't undone", edit);
+        }
+
+        let mut list = list();
+        let mut zones = ConstraintZones(vec![zone(1.0)]);
+        let before = snapshot(&list, &zones);
+        for edit in [
+            Edit::RemoveZone { idx: 0, zone: zone(1.0) },
+            Edit::SetZone { idx: 0, before: zone(1.0), after: zone(2.0) },
+        ] {
+            edit.apply(&mut list, &mut zones, &mut commands);
+            edit.inverse().apply(&mut list, &mut zones, &mut commands);
+            assert_eq!(snapshot(&list, &zones), before, "{:?} wasn't undone", edit);
+        }
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        let mut history = EditHistory::default();
+        let mut list = list();
+        let mut zones = ConstraintZones::default();
+
+        history.perform(Edit::Set { path_id: 0, idx: 0, before: waypoint(0.0), after: waypoint(0.5) }, &mut list, &mut zones, &mut commands);
+        history.undo(&mut list, &mut zones, &mut commands);
+        assert_eq!(history.redo.len(), 1);
+
+        history.perform(Edit::Set { path_id: 0, idx: 1, before: waypoint(1.0), after: waypoint(1.5) }, &mut list, &mut zones, &mut commands);
+        assert!(history.redo.is_empty());
+
+        history.redo(&mut list, &mut zones, &mut commands);
+        assert_eq!(list.0[0], vec![waypoint(0.0), waypoint(1.5), waypoint(2.0)]);
+    }
+
+    #[test]
+    fn oldest_edits_are_dropped() {
+        let world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        let mut history = EditHistory::default();
+        let mut list = list();
+        let mut zones = ConstraintZones::default();
+
+        for i in 0..MAX_HISTORY + 5 {
+            let edit = Edit::Set { path_id: 0, idx: 2, before: waypoint(i as f32), after: waypoint(i as f32 + 1.0) };
+            history.perform(edit, &mut list, &mut zones, &mut commands);
+        }
+        assert_eq!(history.undo.len(), MAX_HISTORY);
+
+        for _ in 0..MAX_HISTORY + 5 {
+            history.undo(&mut list, &mut zones, &mut commands);
+        }
+        assert_eq!(list.0[0][2], waypoint(5.0));
+        assert_eq!(history.redo.len(), MAX_HISTORY);
+    }
+}
@@ -1,6 +1,12 @@
 mod config_panel;
-mod waypoints;
+mod generator;
+pub mod history;
+mod path_formats;
+pub mod routine_file;
+mod snapping;
+pub mod waypoints;
 pub mod trajectory;
+pub mod zones;
 
 use bevy::prelude::*;
 
@@ -9,17 +15,31 @@ pub struct AutoPathingPlugin;
 
 impl Plugin for AutoPathingPlugin {
     fn build(&self, app: &mut App) {
+        app.insert_resource(routine_file::RoutineFilePath::default());
+        app.insert_resource(history::EditHistory::default());
+        app.insert_resource(snapping::Snapping::default());
+        app.insert_resource(zones::ConstraintZones::default());
+        app.insert_resource(zones::ZoneEditor::default());
         app.add_startup_system(config_panel::setup);
         app.add_startup_system(waypoints::setup);
-        app.add_system(config_panel::root_updater);
+        app.add_startup_system(trajectory::setup);
         app.add_system(config_panel::button_system);
         app.add_system(config_panel::config_text_updater);
         app.add_system(waypoints::waypoint_updater);
         app.add_system(waypoints::rotation_anchor_updater);
         app.add_system(waypoints::path_continuity_updater);
         app.add_system(trajectory::trajectory_updater);
+        app.add_system(trajectory::trajectory_generation_starter);
+        app.add_system(trajectory::trajectory_generation_poller);
         app.add_system(trajectory::trajectory_path_updater);
-        app.add_system(waypoints::waypoint_grab_system);
+        app.add_system(trajectory::trajectory_status_updater);
+        app.add_system(waypoints::waypoint_grab_system.label("waypoint_grab"));
+        app.add_system(waypoints::waypoint_edit_system.label("waypoint_edit").after("waypoint_grab"));
+        app.add_system(waypoints::waypoint_nudge_system.after("waypoint_grab"));
+        app.add_system(zones::zone_draw_system.after("waypoint_edit"));
+        app.add_system(zones::zone_overlay_updater);
+        app.add_system(snapping::snap_feature_updater);
+        app.add_system(history::history_keyboard_system);
     }
 }
 
@@ -0,0 +1,336 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use uom::si::acceleration::meter_per_second_squared;
+use uom::si::curvature::radian_per_meter;
+use uom::si::angle::{degree, radian};
+use uom::si::f32::{Acceleration, Angle, Length, Velocity};
+use uom::si::length::meter;
+use uom::si::time::second;
+use uom::si::velocity::meter_per_second;
+
+use crate::auto_pathing::generator::{control_vectors, TrajectoryConfig};
+use crate::auto_pathing::routine_file::RoutineFileError;
+use crate::auto_pathing::trajectory::{generate_trajectory, TimedTrajectory};
+use crate::auto_pathing::waypoints::{FieldWaypointList, PathConstraints, Waypoint};
+use crate::field::{wrap_angle, Field, FieldPose, FieldPosition};
+
+// PathPlanner .path file, meters and degrees with the origin at the bottom left corner of the field like the sim
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathPlannerPath {
+    pub waypoints: Vec<PathPlannerWaypoint>,
+    #[serde(default)]
+    pub max_velocity: Option<f32>,
+    #[serde(default)]
+    pub max_acceleration: Option<f32>,
+    #[serde(default)]
+    pub is_reversed: Option<bool>,
+    #[serde(default)]
+    pub markers: Vec<serde_json::Value>,
+}
+
+// Anchor with the bezier control points either side of it, the ends only have one
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathPlannerWaypoint {
+    pub anchor_point: PathPlannerPoint,
+    pub prev_control: Option<PathPlannerPoint>,
+    pub next_control: Option<PathPlannerPoint>,
+    #[serde(default)]
+    pub holonomic_angle: f32,
+    #[serde(default)]
+    pub is_reversal: bool,
+    #[serde(default)]
+    pub vel_override: Option<f32>,
+    #[serde(default)]
+    pub is_locked: bool,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PathPlannerPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+// One sampled state as WPILib's TrajectoryUtil serializes it, SI units
+#[derive(Serialize, Deserialize)]
+pub struct WpilibState {
+    pub time: f32,
+    pub velocity: f32,
+    pub acceleration: f32,
+    pub pose: WpilibPose,
+    pub curvature: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WpilibPose {
+    pub translation: WpilibTranslation,
+    pub rotation: WpilibRotation,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WpilibTranslation {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WpilibRotation {
+    pub radians: f32,
+}
+
+// Where each tool keeps its paths, relative to the project folder. Both formats use the .path extension,
+// so they never share a folder.
+const PATHPLANNER_DIR: &str = "deploy/pathplanner";
+const PATHWEAVER_DIR: &str = "PathWeaver/Paths";
+
+// An interior holonomic angle this close to the direction of travel is what export writes for a plain translation
+const ROTATION_TARGET_TOLERANCE_DEGREES: f32 = 0.5;
+
+const PATHWEAVER_HEADER: &str = "X,Y,Tangent X,Tangent Y,Fixed Theta,Reversed,Name";
+const TRAJECTORY_CSV_HEADER: &str = "time,x,y,heading,velocity,acceleration,curvature,holonomic_rotation";
+
+// Both formats describe the path with the same splines the sim generates, so exported paths drive identically.
+// A waypoint shared by consecutive routines is written the same way in both files.
+pub fn to_pathplanner(waypoints: &FieldWaypointList, path_id: usize) -> PathPlannerPath {
+    let routine = &waypoints.0[path_id];
+    let max_velocities = &waypoints.2[path_id].max_velocities;
+    let vectors = control_vectors(&generate_trajectory(waypoints, path_id));
+    let last = vectors.len() - 1;
+    let config = waypoints.2[path_id].config;
+
+    let path_waypoints = vectors.iter().zip(routine.iter()).enumerate().map(|(i, ((x, y), waypoint))| {
+        // A bezier control point is a third of the hermite derivative away from the anchor
+        let control = |sign: f32| PathPlannerPoint { x: x[0] + sign * x[1] / 3.0, y: y[0] + sign * y[1] / 3.0 };
+
+        PathPlannerWaypoint {
+            anchor_point: PathPlannerPoint { x: x[0], y: y[0] },
+            prev_control: if i > 0 { Some(control(-1.0)) } else { None },
+            next_control: if i < last { Some(control(1.0)) } else { None },
+            holonomic_angle: match waypoint {
+                Waypoint::Pose(p) => p.rotation.get::<degree>(),
+                Waypoint::Translation(_) => y[1].atan2(x[1]).to_degrees(),
+            },
+            is_reversal: false,
+            vel_override: max_velocities[i].map(|v| v.get::<meter_per_second>()),
+            is_locked: false,
+        }
+    }).collect();
+
+    PathPlannerPath {
+        waypoints: path_waypoints,
+        max_velocity: Some(config.max_velocity.get::<meter_per_second>()),
+        max_acceleration: Some(config.max_acceleration.get::<meter_per_second_squared>()),
+        is_reversed: Some(false),
+        markers: vec![],
+    }
+}
+
+// PathWeaver's origin is the top left corner of the field, so every y is negative
+pub fn to_pathweaver(waypoints: &FieldWaypointList, path_id: usize, field: &Field) -> String {
+    let routine = &waypoints.0[path_id];
+    let vectors = control_vectors(&generate_trajectory(waypoints, path_id));
+    let height = field.size.y.get::<meter>();
+
+    let mut csv = PATHWEAVER_HEADER.to_string() + "\n";
+    for ((x, y), waypoint) in vectors.iter().zip(routine.iter()) {
+        // Headings the user set stay fixed, PathWeaver recomputes the others when the path is edited
+        let fixed = matches!(waypoint, Waypoint::Pose(_));
+        csv += &format!("{},{},{},{},{},false,\n", x[0], y[0] - height, x[1], y[1], fixed);
+    }
+    csv
+}
+
+fn is_pathplanner(contents: &str) -> bool {
+    contents.trim_start().starts_with('{')
+}
+
+// The tool's own folder under dir, unless dir already is that folder
+fn export_dir(dir: &Path, tool_dir: &str) -> Result<PathBuf, RoutineFileError> {
+    let dir = if dir.ends_with(tool_dir) { dir.to_path_buf() } else { dir.join(tool_dir) };
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// Re-exporting replaces an earlier export, but a path in the other format is never overwritten
+fn write_path(path: &Path, contents: &str, pathplanner: bool) -> Result<(), RoutineFileError> {
+    if let Ok(existing) = fs::read_to_string(path) {
+        if is_pathplanner(&existing) != pathplanner {
+            return Err(RoutineFileError::Overwrite(path.to_path_buf()));
+        }
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+// Writes one file per routine into dir/deploy/pathplanner, named like "<name>_<routine>.path"
+pub fn export_pathplanner(dir: &Path, name: &str, waypoints: &FieldWaypointList) -> Result<Vec<PathBuf>, RoutineFileError> {
+    let dir = export_dir(dir, PATHPLANNER_DIR)?;
+    (0..waypoints.0.len()).map(|path_id| {
+        let path = dir.join(format!("{}_{}.path", name, path_id));
+        write_path(&path, &(serde_json::to_string_pretty(&to_pathplanner(waypoints, path_id))? + "\n"), true)?;
+        Ok(path)
+    }).collect()
+}
+
+// Same naming as export_pathplanner, in dir/PathWeaver/Paths
+pub fn export_pathweaver(dir: &Path, name: &str, waypoints: &FieldWaypointList, field: &Field) -> Result<Vec<PathBuf>, RoutineFileError> {
+    let dir = export_dir(dir, PATHWEAVER_DIR)?;
+    (0..waypoints.0.len()).map(|path_id| {
+        let path = dir.join(format!("{}_{}.path", name, path_id));
+        write_path(&path, &to_pathweaver(waypoints, path_id, field), false)?;
+        Ok(path)
+    }).collect()
+}
+
+
+// Same as TrajectoryUtil.serializeTrajectory, so TrajectoryUtil.fromPathweaverJson loads it on the robot
+pub fn to_wpilib_json(trajectory: &TimedTrajectory) -> Result<String, RoutineFileError> {
+    let states: Vec<WpilibState> = trajectory.0.iter().map(|s| WpilibState {
+        time: s.time.get::<second>(),
+        velocity: s.velocity.get::<meter_per_second>(),
+        acceleration: s.acceleration.get::<meter_per_second_squared>(),
+        pose: WpilibPose {
+            translation: WpilibTranslation {
+                x: s.pose.translation.x.get::<meter>(),
+                y: s.pose.translation.y.get::<meter>(),
+            },
+            rotation: WpilibRotation { radians: s.pose.rotation.get::<radian>() },
+        },
+        curvature: s.curvature.get::<radian_per_meter>(),
+    }).collect();
+
+    Ok(serde_json::to_string(&states)?)
+}
+
+// Seconds, meters and radians, one row per sampled state
+pub fn to_trajectory_csv(trajectory: &TimedTrajectory) -> String {
+    let mut csv = TRAJECTORY_CSV_HEADER.to_string() + "\n";
+    for s in trajectory.0.iter() {
+        csv += &format!(
+            "{},{},{},{},{},{},{},{}\n",
+            s.time.get::<second>(),
+            s.pose.translation.x.get::<meter>(),
+            s.pose.translation.y.get::<meter>(),
+            s.pose.rotation.get::<radian>(),
+            s.velocity.get::<meter_per_second>(),
+            s.acceleration.get::<meter_per_second_squared>(),
+            s.curvature.get::<radian_per_meter>(),
+            s.holonomic_rotation.get::<radian>()
+        );
+    }
+    csv
+}
+
+// Trajectories still generating are skipped rather than written empty
+pub fn export_trajectories(
+    dir: &Path,
+    name: &str,
+    trajectories: &[(usize, &TimedTrajectory)],
+    csv: bool
+) -> Result<Vec<PathBuf>, RoutineFileError> {
+    trajectories.iter().filter(|(_, t)| !t.0.is_empty()).map(|(path_id, trajectory)| {
+        let (path, contents) = if csv {
+            (dir.join(format!("{}_{}.csv", name, path_id)), to_trajectory_csv(trajectory))
+        } else {
+            (dir.join(format!("{}_{}.wpilib.json", name, path_id)), to_wpilib_json(trajectory)?)
+        };
+        fs::write(&path, contents)?;
+        Ok(path)
+    }).collect()
+}
+
+// Reads a PathPlanner or PathWeaver .path file, both formats share the extension so the contents decide
+pub fn import(path: &Path, field: &Field) -> Result<(Vec<Waypoint>, PathConstraints), RoutineFileError> {
+    let contents = fs::read_to_string(path)?;
+
+    if is_pathplanner(&contents) {
+        from_pathplanner(&serde_json::from_str(&contents)?)
+    } else {
+        from_pathweaver(&contents, field)
+    }
+}
+
+// The limits and velocity overrides come back as the path's constraints, an interior holonomic angle that isn't just
+// the direction of travel becomes a rotation target
+pub fn from_pathplanner(path: &PathPlannerPath) -> Result<(Vec<Waypoint>, PathConstraints), RoutineFileError> {
+    let points = path.waypoints.iter().map(|w| {
+        let anchor = w.anchor_point;
+        // The path leaves towards the next control point and arrives from the previous one
+        let tangent = match (w.prev_control, w.next_control) {
+            (_, Some(next)) if (next.x, next.y) != (anchor.x, anchor.y) => Some((next.x - anchor.x, next.y - anchor.y)),
+            (Some(prev), _) if (prev.x, prev.y) != (anchor.x, anchor.y) => Some((anchor.x - prev.x, anchor.y - prev.y)),
+            _ => None
+        };
+        let holonomic = w.holonomic_angle.to_radians();
+        let heading = match tangent {
+            Some((dx, dy)) => dy.atan2(dx),
+            None => holonomic,
+        };
+        let rotation = match wrap_angle(holonomic - heading).abs() > ROTATION_TARGET_TOLERANCE_DEGREES.to_radians() {
+            true => Some(holonomic),
+            false => None
+        };
+        (anchor.x, anchor.y, heading, rotation)
+    }).collect();
+
+    let defaults = TrajectoryConfig::default();
+    // Zero or missing limits mean PathPlanner's own defaults, which the sim doesn't know
+    let limit = |value: Option<f32>| value.filter(|v| *v > 0.0);
+    let constraints = PathConstraints {
+        config: TrajectoryConfig {
+            max_velocity: limit(path.max_velocity).map_or(defaults.max_velocity, Velocity::new::<meter_per_second>),
+            max_acceleration: limit(path.max_acceleration).map_or(defaults.max_acceleration, Acceleration::new::<meter_per_second_squared>),
+            ..defaults
+        },
+        max_velocities: path.waypoints.iter().map(|w| limit(w.vel_override).map(Velocity::new::<meter_per_second>)).collect(),
+        ..Default::default()
+    };
+
+    Ok((to_waypoints(points)?, constraints))
+}
+
+// PathWeaver paths carry no limits, they get the defaults
+pub fn from_pathweaver(csv: &str, field: &Field) -> Result<(Vec<Waypoint>, PathConstraints), RoutineFileError> {
+    let height = field.size.y.get::<meter>();
+
+    let points: Vec<(f32, f32, f32, Option<f32>)> = csv.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with(PATHWEAVER_HEADER))
+        .enumerate()
+        .map(|(i, line)| {
+            let values: Vec<f32> = line.split(',').take(4).map(|v| v.trim().parse::<f32>()).collect::<Result<_, _>>()
+                .map_err(|e| RoutineFileError::Invalid(format!("PathWeaver point {}: {}", i, e)))?;
+            match values[..] {
+                [x, y, tx, ty] => Ok((x, y + height, ty.atan2(tx), None)),
+                _ => Err(RoutineFileError::Invalid(format!("PathWeaver point {} has fewer than 4 columns", i)))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let constraints = PathConstraints {
+        max_velocities: vec![None; points.len()],
+        ..Default::default()
+    };
+
+    Ok((to_waypoints(points)?, constraints))
+}
+
+// The ends keep their headings, interior points are only poses when they have a rotation target.
+// PathWeaver headings are directions of travel, the splines through the interior points are recomputed from them.
+fn to_waypoints(points: Vec<(f32, f32, f32, Option<f32>)>) -> Result<Vec<Waypoint>, RoutineFileError> {
+    if points.len() < 2 {
+        return Err(RoutineFileError::Invalid("a path needs at least 2 waypoints".to_string()));
+    }
+
+    let last = points.len() - 1;
+    Ok(points.into_iter().enumerate().map(|(i, (x, y, heading, rotation))| {
+        let position = FieldPosition::new(Length::new::<meter>(x), Length::new::<meter>(y));
+        match rotation {
+            _ if i == 0 || i == last => Waypoint::Pose(FieldPose::new(position, Angle::new::<radian>(heading))),
+            Some(rotation) => Waypoint::Pose(FieldPose::new(position, Angle::new::<radian>(rotation))),
+            None => Waypoint::Translation(position)
+        }
+    }).collect())
+}
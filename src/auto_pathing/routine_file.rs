@@ -0,0 +1,174 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use bevy::log::warn;
+use native_dialog::FileDialog;
+use serde::{Deserialize, Serialize};
+
+use crate::auto_pathing::waypoints::{PathConstraints, Waypoint};
+use crate::auto_pathing::zones::ConstraintZone;
+
+// Bump when the file layout changes, older files are still read as long as they can be converted
+pub const ROUTINE_FILE_VERSION: u32 = 1;
+
+// All routines as written to disk, lengths in meters and angles in radians
+#[derive(Serialize, Deserialize)]
+pub struct RoutineFile {
+    pub version: u32,
+    pub routines: Vec<Vec<Waypoint>>,
+    // One per routine, files written before constraints existed have none
+    #[serde(default)]
+    pub constraints: Vec<PathConstraints>,
+    #[serde(default)]
+    pub zones: Vec<ConstraintZone>,
+}
+
+// File the routines were last opened from or saved to, Save writes back to it
+#[derive(Default)]
+pub struct RoutineFilePath(pub Option<PathBuf>);
+
+#[derive(Debug)]
+pub enum RoutineFileError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Version(u32),
+    Invalid(String),
+    // An export would replace a file it didn't write
+    Overwrite(PathBuf),
+}
+
+impl fmt::Display for RoutineFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutineFileError::Io(e) => write!(f, "io error: {}", e),
+            RoutineFileError::Json(e) => write!(f, "json error: {}", e),
+            RoutineFileError::Version(v) => write!(f, "file version {} is newer than supported version {}", v, ROUTINE_FILE_VERSION),
+            RoutineFileError::Invalid(msg) => write!(f, "invalid routine file: {}", msg),
+            RoutineFileError::Overwrite(path) => write!(f, "{} holds a path in another format, not overwriting it", path.display()),
+        }
+    }
+}
+
+impl From<std::io::Error> for RoutineFileError {
+    fn from(e: std::io::Error) -> Self {
+        RoutineFileError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RoutineFileError {
+    fn from(e: serde_json::Error) -> Self {
+        RoutineFileError::Json(e)
+    }
+}
+
+pub fn save(
+    path: &Path,
+    routines: &[Vec<Waypoint>],
+    constraints: &[PathConstraints],
+    zones: &[ConstraintZone]
+) -> Result<(), RoutineFileError> {
+    let file = RoutineFile {
+        version: ROUTINE_FILE_VERSION,
+        routines: routines.to_vec(),
+        constraints: constraints.to_vec(),
+        zones: zones.to_vec(),
+    };
+    // Pretty printed so diffs stay readable in the robot repo
+    fs::write(path, serde_json::to_string_pretty(&file)? + "\n")?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<RoutineFile, RoutineFileError> {
+    let file: RoutineFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    if file.version > ROUTINE_FILE_VERSION {
+        return Err(RoutineFileError::Version(file.version));
+    }
+    if file.routines.is_empty() {
+        return Err(RoutineFileError::Invalid("no routines".to_string()));
+    }
+    // The editor and generator assume every path has both ends
+    if let Some(i) = file.routines.iter().position(|r| r.len() < 2) {
+        return Err(RoutineFileError::Invalid(format!("routine {} has fewer than 2 waypoints", i)));
+    }
+    if let Some(i) = file.constraints.iter().zip(file.routines.iter()).position(|(c, r)| c.max_velocities.len() != r.len()) {
+        return Err(RoutineFileError::Invalid(format!("routine {} has constraints for a different number of waypoints", i)));
+    }
+    if let Some(i) = file.zones.iter().position(|z| z.shape.vertices().len() < 3) {
+        return Err(RoutineFileError::Invalid(format!("zone {} has fewer than 3 corners", i)));
+    }
+
+    Ok(file)
+}
+
+fn dialog(current: &RoutineFilePath) -> FileDialog<'_> {
+    let dialog = FileDialog::new().add_filter("Routines", &["json"]);
+    match current.0.as_ref().and_then(|p| p.parent()) {
+        Some(dir) if dir.is_dir() => dialog.set_location(dir),
+        _ => dialog.set_filename("routines.json"),
+    }
+}
+
+// Blocks until the user picks a file, None if they cancelled
+pub fn pick_open_path(current: &RoutineFilePath) -> Option<PathBuf> {
+    match dialog(current).show_open_single_file() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Could not show the open dialog: {}", e);
+            None
+        }
+    }
+}
+
+pub fn pick_save_path(current: &RoutineFilePath) -> Option<PathBuf> {
+    match dialog(current).show_save_single_file() {
+        Ok(path) => path.map(|p| if p.extension().is_none() { p.with_extension("json") } else { p }),
+        Err(e) => {
+            warn!("Could not show the save dialog: {}", e);
+            None
+        }
+    }
+}
+
+// PathPlanner or PathWeaver paths to import, in file name order so numbered paths chain up
+pub fn pick_import_paths(current: &RoutineFilePath) -> Vec<PathBuf> {
+    let dialog = FileDialog::new().add_filter("PathPlanner and PathWeaver paths", &["path"]);
+    let dialog = match current.0.as_ref().and_then(|p| p.parent()) {
+        Some(dir) if dir.is_dir() => dialog.set_location(dir),
+        _ => dialog,
+    };
+    match dialog.show_open_multiple_file() {
+        Ok(mut paths) => {
+            paths.sort();
+            paths
+        }
+        Err(e) => {
+            warn!("Could not show the import dialog: {}", e);
+            vec![]
+        }
+    }
+}
+
+// Directory to export into, starting next to the routine file
+pub fn pick_export_dir(current: &RoutineFilePath) -> Option<PathBuf> {
+    let dialog = FileDialog::new();
+    let dialog = match current.0.as_ref().and_then(|p| p.parent()) {
+        Some(dir) if dir.is_dir() => dialog.set_location(dir),
+        _ => dialog,
+    };
+    match dialog.show_open_single_dir() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Could not show the directory dialog: {}", e);
+            None
+        }
+    }
+}
+
+// Base name for files exported from the routines, taken from the routine file
+pub fn export_name(current: &RoutineFilePath) -> String {
+    match current.0.as_ref().and_then(|p| p.file_stem()) {
+        Some(stem) => stem.to_string_lossy().to_string(),
+        None => "routine".to_string(),
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use uom::si::angle::degree;
+use uom::si::f32::{Angle, Length};
+use uom::si::length::meter;
+
+use crate::auto_pathing::zones::ZoneOverlay;
+use crate::field::{FieldPose, FieldPosition};
+use crate::field::objects::Hub;
+use crate::field::shapes::{FieldCircle, FieldPath};
+
+// Grid sizes the config panel steps through, in meters
+const GRID_SIZES: [f32; 5] = [0.05, 0.1, 0.25, 0.5, 1.0];
+const HEADING_STEP_DEGREES: f32 = 15.0;
+// Nudge distance when the grid is off
+const FINE_NUDGE_METERS: f32 = 0.01;
+
+// How dragged and nudged waypoints are snapped, each kind is off until it is toggled on from the config panel
+pub struct Snapping {
+    pub grid: bool,
+    grid_size: usize,
+    pub features: bool,
+    pub heading: bool,
+    // Tarmac corners and hub circles, collected from the field objects
+    corners: Vec<FieldPosition>,
+    circles: Vec<(FieldPosition, Length)>,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self {
+            grid: false,
+            grid_size: 2,
+            features: false,
+            heading: false,
+            corners: vec![],
+            circles: vec![],
+        }
+    }
+}
+
+impl Snapping {
+    pub fn grid_size(&self) -> Length {
+        Length::new::<meter>(GRID_SIZES[self.grid_size])
+    }
+
+    pub fn finer_grid(&mut self) {
+        self.grid_size = self.grid_size.saturating_sub(1);
+    }
+
+    pub fn coarser_grid(&mut self) {
+        self.grid_size = (self.grid_size + 1).min(GRID_SIZES.len() - 1);
+    }
+
+    // A field feature within tolerance wins over the grid
+    pub fn snap_position(&self, pos: FieldPosition, tolerance: Length) -> FieldPosition {
+        if self.features {
+            if let Some(feature) = self.nearest_feature(&pos) {
+                if feature.dist(&pos) <= tolerance {
+                    return feature;
+                }
+            }
+        }
+        self.snap_to_grid(pos)
+    }
+
+    pub fn snap_to_grid(&self, pos: FieldPosition) -> FieldPosition {
+        if !self.grid {
+            return pos;
+        }
+        let size = GRID_SIZES[self.grid_size];
+        let round = |l: Length| Length::new::<meter>((l.get::<meter>() / size).round() * size);
+        FieldPosition::new(round(pos.x), round(pos.y))
+    }
+
+    pub fn snap_heading(&self, heading: Angle) -> Angle {
+        if !self.heading {
+            return heading;
+        }
+        Angle::new::<degree>((heading.get::<degree>() / HEADING_STEP_DEGREES).round() * HEADING_STEP_DEGREES)
+    }
+
+    // One arrow key press moves a waypoint by a grid cell, or a centimeter without the grid
+    pub fn nudge_step(&self) -> Length {
+        if self.grid {
+            self.grid_size()
+        } else {
+            Length::new::<meter>(FINE_NUDGE_METERS)
+        }
+    }
+
+    fn nearest_feature(&self, pos: &FieldPosition) -> Option<FieldPosition> {
+        // The closest point on a circle is along the line from its center
+        let on_circles = self.circles.iter().filter_map(|(center, radius)| {
+            let d = pos.dist(center);
+            if d.get::<meter>() == 0.0 {
+                return None;
+            }
+            let scale = radius.get::<meter>() / d.get::<meter>();
+            Some(FieldPosition::new(
+                center.x + (pos.x - center.x) * scale,
+                center.y + (pos.y - center.y) * scale
+            ))
+        });
+
+        self.corners.iter().copied()
+            .chain(on_circles)
+            .min_by(|a, b| a.dist(pos).get::<meter>().total_cmp(&b.dist(pos).get::<meter>()))
+    }
+}
+
+type FeatureChangedFilter = Or<(Changed<FieldPath>, Changed<FieldCircle>)>;
+
+// Field objects are spawned at startup, so the features are picked up once they exist
+pub fn snap_feature_updater(
+    mut snapping: ResMut<Snapping>,
+    // Zone corners aren't field features
+    paths: Query<&FieldPath, Without<ZoneOverlay>>,
+    hubs: Query<(&FieldPose, &FieldCircle), With<Hub>>,
+    changed: Query<(), FeatureChangedFilter>
+) {
+    if changed.is_empty() {
+        return;
+    }
+
+    snapping.corners = paths.iter().flat_map(|p| p.vertices()).collect();
+    snapping.circles = hubs.iter().map(|(pose, circle)| (pose.translation, circle.0)).collect();
+}
@@ -1,45 +1,318 @@
-use std::env;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
 use bevy::prelude::*;
-use bevy_prototype_lyon::entity::ShapeBundle;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
 use bevy_prototype_lyon::prelude::*;
-use pyo3::Python;
-use pyo3::types::{PyFunction, PyList};
+use futures_lite::future;
 use uom::ConstZero;
 use uom::si::angle::radian;
-use uom::si::f32::*;
+use uom::si::f32::{Acceleration, Angle, Curvature, Time, Velocity};
 use uom::si::length::meter;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use crate::auto_pathing::generator::{self, TrajectoryConfig};
 use crate::auto_pathing::waypoints::{FieldWaypointList, Waypoint};
+use crate::auto_pathing::zones::{ConstraintZone, ConstraintZones, ZoneShape};
 use crate::field::{Field, FieldPose, FieldPosition};
-use crate::{Layout, RobotClient};
+use crate::field::render::FieldZ;
+use crate::layout::render::FONT_SIZE;
+use crate::Layout;
+use crate::robot_connection::{ConnectionStatus, RobotBackend, RobotClientError, RobotConnection};
 
-#[derive(Component, Default, Serialize)]
+#[derive(Component, Default, Clone, PartialEq, Serialize)]
 pub struct Trajectory {
     pub start: FieldPose,
     pub points: Vec<FieldPosition>,
-    pub end: FieldPose
+    pub end: FieldPose,
+    pub start_velocity: Velocity,
+    pub end_velocity: Velocity,
+    // Speed cap through each of the points, same order as points
+    pub point_max_velocities: Vec<Option<Velocity>>,
+    // Where the robot should face at each of the points, same order as points
+    pub rotation_targets: Vec<Option<Angle>>,
+    // Every zone is sent, the generator checks which ones the path passes through
+    pub zones: Vec<ConstraintZone>,
+    pub config: TrajectoryConfig,
 }
 
 #[derive(Component)]
 pub struct TrajectoryID(pub usize);
 
-pub fn build_trajectory_path(trajectory: &Trajectory, field: &Field, layout: &Layout, client: &mut RobotClient) -> Path {
-    let points = client.gen_trajectory(trajectory);
+// One sample of a time-parameterized trajectory, the pose rotation is the direction of travel
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectoryState {
+    pub time: Time,
+    pub pose: FieldPose,
+    // Where the swerve faces, which can differ from the direction of travel
+    pub holonomic_rotation: Angle,
+    pub velocity: Velocity,
+    pub acceleration: Acceleration,
+    pub curvature: Curvature,
+}
+
+// Most recently generated states for a trajectory entity, ordered by time
+#[derive(Component, Default, Clone)]
+pub struct TimedTrajectory(pub Vec<TrajectoryState>);
+
+// What a generation produced, along with the trajectory it was started for and the backend that generated it
+pub struct GeneratedTrajectory {
+    trajectory: Trajectory,
+    timed: TimedTrajectory,
+    // None for the native generator, which remote generation falls back to when it fails
+    source: Option<RobotBackend>,
+}
+
+// In-flight generation for a trajectory entity, keyed by the trajectory it was started for
+#[derive(Component, Default)]
+pub struct TrajectoryTask(Option<(u64, Task<GeneratedTrajectory>)>);
+
+#[derive(Component)]
+pub struct TrajectoryStatusText;
+
+struct CachedTrajectory {
+    // Compared on lookup, so two trajectories with the same hash never share states
+    trajectory: Trajectory,
+    timed: TimedTrajectory,
+    last_used: u64,
+}
+
+// Generated trajectories keyed by the generator source and the hash of the trajectory they were generated from.
+// Once full, the least recently used entry makes room for the new one.
+#[derive(Default)]
+pub struct TrajectoryCache {
+    entries: HashMap<(Option<RobotBackend>, u64), CachedTrajectory>,
+    // Bumped on every lookup and insert
+    clock: u64,
+}
+
+const MAX_CACHED_TRAJECTORIES: usize = 256;
+
+// Heading ticks are drawn this far apart along the path
+const HEADING_TICK_SPACING_M: f32 = 0.5;
+const HEADING_TICK_LENGTH: f32 = 12.0;
+
+const PATH_COLOR: Color = Color::WHITE;
+const GENERATING_PATH_COLOR: Color = Color::DARK_GRAY;
+
+impl Trajectory {
+    // Hash of everything the generators read, floats by their bits
+    fn cache_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        hash_pose(&mut hasher, &self.start);
+        hasher.write_usize(self.points.len());
+        for p in &self.points {
+            hash_position(&mut hasher, p);
+        }
+        hash_pose(&mut hasher, &self.end);
+        hash_f32(&mut hasher, self.start_velocity.value);
+        hash_f32(&mut hasher, self.end_velocity.value);
+        for v in &self.point_max_velocities {
+            hash_option(&mut hasher, v.map(|v| v.value));
+        }
+        for r in &self.rotation_targets {
+            hash_option(&mut hasher, r.map(|r| r.value));
+        }
+        hasher.write_usize(self.zones.len());
+        for z in &self.zones {
+            match &z.shape {
+                ZoneShape::Rectangle { min, max } => {
+                    hasher.write_u8(0);
+                    hash_position(&mut hasher, min);
+                    hash_position(&mut hasher, max);
+                }
+                ZoneShape::Polygon(corners) => {
+                    hasher.write_u8(1);
+                    hasher.write_usize(corners.len());
+                    for c in corners {
+                        hash_position(&mut hasher, c);
+                    }
+                }
+            }
+            hash_option(&mut hasher, z.max_velocity.map(|v| v.value));
+            hash_option(&mut hasher, z.max_acceleration.map(|a| a.value));
+        }
+        hash_f32(&mut hasher, self.config.max_velocity.value);
+        hash_f32(&mut hasher, self.config.max_acceleration.value);
+        hash_f32(&mut hasher, self.config.max_angular_velocity.value);
+
+        hasher.finish()
+    }
+}
+
+fn hash_f32(hasher: &mut DefaultHasher, value: f32) {
+    hasher.write_u32(value.to_bits());
+}
+
+fn hash_option(hasher: &mut DefaultHasher, value: Option<f32>) {
+    match value {
+        None => hasher.write_u8(0),
+        Some(v) => {
+            hasher.write_u8(1);
+            hash_f32(hasher, v);
+        }
+    }
+}
+
+fn hash_position(hasher: &mut DefaultHasher, position: &FieldPosition) {
+    hash_f32(hasher, position.x.value);
+    hash_f32(hasher, position.y.value);
+}
+
+fn hash_pose(hasher: &mut DefaultHasher, pose: &FieldPose) {
+    hash_position(hasher, &pose.translation);
+    hash_f32(hasher, pose.rotation.value);
+}
+
+impl TrajectoryCache {
+    fn get(&mut self, source: Option<RobotBackend>, key: u64, trajectory: &Trajectory) -> Option<TimedTrajectory> {
+        self.clock += 1;
+        match self.entries.get_mut(&(source, key)) {
+            Some(entry) if entry.trajectory == *trajectory => {
+                entry.last_used = self.clock;
+                Some(entry.timed.clone())
+            }
+            _ => None
+        }
+    }
+
+    fn insert(&mut self, source: Option<RobotBackend>, key: u64, trajectory: Trajectory, timed: TimedTrajectory) {
+        self.clock += 1;
+
+        if self.entries.len() >= MAX_CACHED_TRAJECTORIES && !self.entries.contains_key(&(source, key)) {
+            let oldest = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| *k);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert((source, key), CachedTrajectory { trajectory, timed, last_used: self.clock });
+    }
+
+    // Drops everything one backend generated, the native entries stay valid across reconnects
+    fn invalidate(&mut self, source: Option<RobotBackend>) {
+        self.entries.retain(|(s, _), _| *s != source);
+    }
+}
+
+impl TrajectoryState {
+    // Pose of the robot itself, facing its holonomic rotation
+    pub fn robot_pose(&self) -> FieldPose {
+        FieldPose::new(self.pose.translation, self.holonomic_rotation)
+    }
+}
+
+impl TimedTrajectory {
+    pub fn total_time(&self) -> Time {
+        self.0.last().map_or(Time::ZERO, |s| s.time)
+    }
+
+    // Linearly interpolates between the two states around t, clamped to the ends of the trajectory
+    pub fn sample(&self, t: Time) -> Option<TrajectoryState> {
+        let idx = self.0.partition_point(|s| s.time < t);
+
+        if idx == 0 {
+            return self.0.first().copied();
+        }
+        if idx >= self.0.len() {
+            return self.0.last().copied();
+        }
+
+        let (prev, next) = (&self.0[idx - 1], &self.0[idx]);
+        let span = next.time - prev.time;
+        let k: f32 = if span > Time::ZERO { ((t - prev.time) / span).value } else { 1.0 };
+
+        Some(TrajectoryState {
+            time: t,
+            pose: prev.pose.interpolate(&next.pose, k),
+            holonomic_rotation: prev.robot_pose().interpolate(&next.robot_pose(), k).rotation,
+            velocity: prev.velocity + (next.velocity - prev.velocity) * k,
+            acceleration: prev.acceleration,
+            curvature: prev.curvature + (next.curvature - prev.curvature) * k,
+        })
+    }
+}
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TrajectoryCache::default());
+
+    commands.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "generating...",
+            TextStyle {
+                font: asset_server.load("fonts/JetBrainsMono-Bold.ttf"),
+                font_size: FONT_SIZE * 0.6,
+                color: Color::GRAY,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Bottom,
+                horizontal: HorizontalAlign::Left,
+            },
+        ),
+        visibility: Visibility { is_visible: false },
+        ..Default::default()
+    }).insert(TrajectoryStatusText);
+}
+
+pub fn spawn_trajectory(commands: &mut Commands, path_id: usize) {
+    let default_shape = shapes::Circle::default();
+    commands.spawn_bundle(GeometryBuilder::build_as(
+        &default_shape,
+        DrawMode::Stroke(StrokeMode::new(PATH_COLOR, 2.0)),
+        Transform::from_xyz(0.0, 0.0, FieldZ::AUTO_PATH.0)
+    ))
+        .insert(Trajectory::default())
+        .insert(TrajectoryID(path_id))
+        .insert(TimedTrajectory::default())
+        .insert(TrajectoryTask::default());
+}
+
+// Prefers the robot code's generator, falling back to the native one while disconnected
+// Also returns which backend generated the states, None for the native generator
+pub fn generate_timed_trajectory(trajectory: &Trajectory, connection: &RobotConnection) -> (TimedTrajectory, Option<RobotBackend>) {
+    match connection.gen_trajectory(trajectory) {
+        Ok(states) => return (states, connection.backend()),
+        Err(RobotClientError::Disconnected) => {}
+        Err(e) => warn!("Remote trajectory generation failed, using native generator: {}", e)
+    }
+
+    (generator::generate(trajectory).unwrap_or_default(), None)
+}
 
+pub fn build_trajectory_path(trajectory: &TimedTrajectory, field: &Field, layout: &Layout) -> Path {
     let mut builder = PathBuilder::new();
 
-    if points.len() == 0 {
+    let states = &trajectory.0;
+
+    if states.is_empty() {
         builder.move_to(Vec2::new(0.0, 0.0));
         return builder.build();
     }
 
-    builder.move_to(field.to_screen_vec(layout, &points[0]));
+    builder.move_to(field.to_screen_vec(layout, &states[0].pose.translation));
 
-    // TODO Dont draw line to first point
-    for p in &points {
-        builder.line_to(field.to_screen_vec(layout, p));
+    for s in &states[1..] {
+        builder.line_to(field.to_screen_vec(layout, &s.pose.translation));
+    }
+
+    // Short ticks pointing where the robot faces, evenly spaced by distance and always at both ends
+    let mut next_tick = 0.0;
+    let mut distance = 0.0;
+    for (i, s) in states.iter().enumerate() {
+        if i > 0 {
+            distance += s.pose.translation.dist(&states[i - 1].pose.translation).get::<meter>();
+        }
+        if distance < next_tick && i < states.len() - 1 {
+            continue;
+        }
+        next_tick = distance + HEADING_TICK_SPACING_M;
+
+        let start = field.to_screen_vec(layout, &s.pose.translation);
+        let (sin, cos) = s.holonomic_rotation.get::<radian>().sin_cos();
+        builder.move_to(start);
+        builder.line_to(start + Vec2::new(cos, sin) * HEADING_TICK_LENGTH);
     }
 
     builder.build()
@@ -53,12 +326,20 @@ pub fn generate_trajectory(waypoints: &FieldWaypointList, path_id: usize) -> Tra
     let mut points: Vec<FieldPosition> = Vec::with_capacity(waypoints.0.len());
 
     let internal_waypoints = &waypoints.0[path_id][1..waypoints.0[path_id].len()-1];
+    let constraints = &waypoints.2[path_id];
+
+    let mut rotation_targets: Vec<Option<Angle>> = Vec::with_capacity(internal_waypoints.len());
 
     for w in internal_waypoints {
         points.push(match w {
             Waypoint::Translation(t) => { *t }
             Waypoint::Pose(pose) => { pose.translation }
-        })
+        });
+        // An interior pose only sets where the robot faces, the path still curves smoothly through it
+        rotation_targets.push(match w {
+            Waypoint::Translation(_) => { None }
+            Waypoint::Pose(pose) => { Some(pose.rotation) }
+        });
     }
 
     Trajectory {
@@ -70,14 +351,24 @@ pub fn generate_trajectory(waypoints: &FieldWaypointList, path_id: usize) -> Tra
         end: match waypoints.0[path_id].last().unwrap() {
             Waypoint::Translation(t) => { FieldPose::new(*t, Angle::ZERO) }
             Waypoint::Pose(p) => { *p }
-        }
+        },
+        start_velocity: constraints.start_velocity,
+        end_velocity: constraints.end_velocity,
+        // A cap list out of step with the waypoints falls back to no cap rather than crashing
+        point_max_velocities: (1..waypoints.0[path_id].len() - 1)
+            .map(|i| constraints.max_velocities.get(i).copied().flatten())
+            .collect(),
+        rotation_targets,
+        zones: vec![],
+        config: constraints.config
     }
 }
 
 pub fn trajectory_updater(
     mut commands: Commands,
     mut query: Query<(Entity, &mut Trajectory, &TrajectoryID, &mut Visibility)>,
-    waypoints: Res<FieldWaypointList>
+    waypoints: Res<FieldWaypointList>,
+    zones: Res<ConstraintZones>
 ) {
     for i in query.iter_mut() {
         let (entity, mut trajectory, id, mut visibility): (Entity, Mut<Trajectory>, &TrajectoryID, Mut<Visibility>) = i;
@@ -87,7 +378,7 @@ pub fn trajectory_updater(
             continue;
         }
         
-        if !waypoints.1 == id.0 {
+        if waypoints.1 != id.0 {
             *visibility = Visibility {
                 is_visible: false
             }
@@ -97,13 +388,133 @@ pub fn trajectory_updater(
             }
         }
 
-        *trajectory = generate_trajectory(&waypoints, id.0);
+        // Only touch the component when the waypoints actually moved so generation isn't retriggered
+        let mut new_trajectory = generate_trajectory(&waypoints, id.0);
+        new_trajectory.zones = zones.0.clone();
+        if *trajectory != new_trajectory {
+            *trajectory = new_trajectory;
+        }
     }
 }
 
-pub fn trajectory_path_updater(mut query: Query<(&Trajectory, &mut Path)>, field: Res<Field>, layout: Res<Layout>, mut client: ResMut<RobotClient>) {
+pub fn trajectory_generation_starter(
+    mut query: Query<(ChangeTrackers<Trajectory>, &Trajectory, &mut TimedTrajectory, &mut TrajectoryTask)>,
+    mut cache: ResMut<TrajectoryCache>,
+    thread_pool: Res<AsyncComputeTaskPool>,
+    connection: Res<RobotConnection>,
+    mut last_status: Local<Option<ConnectionStatus>>
+) {
+    // Paths are regenerated by whichever source takes over, the robot code may have changed while it was disconnected
+    let status = connection.status();
+    let status_changed = *last_status != Some(status);
+    if status_changed {
+        if *last_status == Some(ConnectionStatus::Connected) {
+            cache.invalidate(connection.backend());
+        }
+        *last_status = Some(status);
+    }
+    let source = match status {
+        ConnectionStatus::Connected => connection.backend(),
+        _ => None
+    };
+
     for i in query.iter_mut() {
-        let (trajectory, mut path): (&Trajectory, Mut<Path>) = i;
-        *path = build_trajectory_path(trajectory, &field, &layout, &mut client);
+        let (tracker, trajectory, mut timed, mut task): (ChangeTrackers<Trajectory>, &Trajectory, Mut<TimedTrajectory>, Mut<TrajectoryTask>) = i;
+
+        if !(tracker.is_changed() || status_changed) {
+            continue;
+        }
+
+        let key = trajectory.cache_key();
+
+        if let Some(cached) = cache.get(source, key, trajectory) {
+            *timed = cached;
+            // Dropping a pending task cancels it
+            task.0 = None;
+            continue;
+        }
+
+        let trajectory = trajectory.clone();
+        let connection = connection.clone();
+
+        task.0 = Some((key, thread_pool.spawn(async move {
+            let (timed, source) = generate_timed_trajectory(&trajectory, &connection);
+            GeneratedTrajectory { trajectory, timed, source }
+        })));
     }
-}
\ No newline at end of file
+}
+
+pub fn trajectory_generation_poller(
+    mut query: Query<(&mut TimedTrajectory, &mut TrajectoryTask)>,
+    mut cache: ResMut<TrajectoryCache>,
+    connection: Res<RobotConnection>
+) {
+    for i in query.iter_mut() {
+        let (mut timed, mut task): (Mut<TimedTrajectory>, Mut<TrajectoryTask>) = i;
+
+        let result = match &mut task.0 {
+            Some((_, t)) => future::block_on(future::poll_once(t)),
+            None => continue
+        };
+
+        if let Some(generated) = result {
+            let (key, _) = task.0.take().unwrap();
+
+            // A remote result that lands after the connection dropped would outlive the invalidation
+            if generated.source.is_none() || connection.status() == ConnectionStatus::Connected {
+                cache.insert(generated.source, key, generated.trajectory, generated.timed.clone());
+            }
+
+            *timed = generated.timed;
+        }
+    }
+}
+
+pub fn trajectory_path_updater(
+    mut query: Query<(ChangeTrackers<TimedTrajectory>, &TimedTrajectory, &TrajectoryTask, &mut Path, &mut DrawMode)>,
+    field: Res<Field>,
+    layout: Res<Layout>
+) {
+    for i in query.iter_mut() {
+        let (tracker, timed, task, mut path, mut draw_mode): (ChangeTrackers<TimedTrajectory>, &TimedTrajectory, &TrajectoryTask, Mut<Path>, Mut<DrawMode>) = i;
+
+        if tracker.is_changed() || layout.is_changed() {
+            *path = build_trajectory_path(timed, &field, &layout);
+        }
+
+        // Keep showing the stale path, dimmed, while a new one is generated
+        let color = if task.0.is_some() { GENERATING_PATH_COLOR } else { PATH_COLOR };
+        if let DrawMode::Stroke(stroke) = *draw_mode {
+            if stroke.color != color {
+                *draw_mode = DrawMode::Stroke(StrokeMode::new(color, 2.0));
+            }
+        }
+    }
+}
+
+pub fn trajectory_status_updater(
+    mut text_query: Query<(&mut Transform, &mut Visibility), With<TrajectoryStatusText>>,
+    query: Query<(&Trajectory, &TrajectoryID, &TrajectoryTask)>,
+    waypoints: Res<FieldWaypointList>,
+    field: Res<Field>,
+    layout: Res<Layout>
+) {
+    let active = query.iter().find(|(_, id, _)| id.0 == waypoints.1);
+
+    for i in text_query.iter_mut() {
+        let (mut transform, mut visibility): (Mut<Transform>, Mut<Visibility>) = i;
+
+        match active {
+            Some((trajectory, _, task)) if task.0.is_some() => {
+                *transform = Transform::from_translation(
+                    field.to_screen_vec(&layout, &trajectory.start.translation).extend(FieldZ::AUTO_WAYPOINTS.0)
+                        + Vec3::new(15.0, 15.0, 0.0)
+                );
+                visibility.is_visible = true;
+            }
+            _ => {
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
@@ -1,36 +1,53 @@
 use bevy::input::ElementState;
-use bevy::input::mouse::{MouseButtonInput, MouseMotion, MouseWheel};
+use bevy::input::mouse::MouseButtonInput;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
+use serde::{Deserialize, Serialize};
 use uom::ConstZero;
 use uom::si::angle::radian;
 use uom::si::f32::*;
 use uom::si::length::meter;
-use crate::auto_pathing::trajectory::{generate_trajectory, Trajectory, TrajectoryID};
+use crate::auto_pathing::generator::TrajectoryConfig;
+use crate::auto_pathing::history::{Edit, EditHistory};
+use crate::auto_pathing::snapping::Snapping;
+use crate::auto_pathing::trajectory::{spawn_trajectory, TimedTrajectory, TrajectoryID, TrajectoryState};
+use crate::auto_pathing::zones::ConstraintZones;
 
 use crate::field::{Field, FieldPose, FieldPosition};
 use crate::field::render::FieldZ;
-use crate::field::shapes::FieldPath;
 use crate::Layout;
 
 const WAYPOINT_RADIUS: f32 = 15.0;
 const ROTATION_ANCHOR_POINT_RADIUS: f32 = 10.0;
 const ROTATION_ANCHOR_REVOLUTION_RADIUS: f32 = 25.0;
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Waypoint {
     Translation(FieldPosition),
     Pose(FieldPose),
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct FieldWaypointID {
     path_id: usize,
     idx: usize
 }
 
+// Velocity limits for one path, kept beside its waypoints
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathConstraints {
+    // Files saved before paths had their own limits get the defaults
+    #[serde(default)]
+    pub config: TrajectoryConfig,
+    // Non-zero lets chained paths flow into each other without stopping
+    pub start_velocity: Velocity,
+    pub end_velocity: Velocity,
+    // Cap on the speed through each waypoint, same indices as the waypoints
+    pub max_velocities: Vec<Option<Velocity>>,
+}
+
 #[derive(Default)]
-pub struct FieldWaypointList(pub Vec<Vec<Waypoint>>, pub usize);
+pub struct FieldWaypointList(pub Vec<Vec<Waypoint>>, pub usize, pub Vec<PathConstraints>);
 
 #[derive(Component)]
 pub struct FieldWaypoint(FieldWaypointID);
@@ -38,9 +55,6 @@ pub struct FieldWaypoint(FieldWaypointID);
 #[derive(Component)]
 pub struct FieldRotationAnchor(FieldWaypointID);
 
-#[derive(Component)]
-pub struct DrawnTrajectory;
-
 pub fn setup(mut commands: Commands) {
     let mut list = FieldWaypointList::default();
 
@@ -54,20 +68,6 @@ pub fn setup(mut commands: Commands) {
         )
     );
 
-    let wp2 = Waypoint::Translation(
-        FieldPosition::new(
-            Length::new::<meter>(3.0),
-            Length::new::<meter>(2.0)
-        )
-    );
-
-    let wp3 = Waypoint::Translation(
-        FieldPosition::new(
-            Length::new::<meter>(4.0),
-            Length::new::<meter>(1.0)
-        )
-    );
-
     let wp4 = Waypoint::Pose(
         FieldPose::new(
             FieldPosition::new(
@@ -79,26 +79,13 @@ pub fn setup(mut commands: Commands) {
     );
 
     spawn_waypoint(wp1, &mut list, &mut commands, 0);
-    // spawn_waypoint(wp2, &mut list, &mut commands, 0);
-    // spawn_waypoint(wp3, &mut list, &mut commands, 0);
     spawn_waypoint(wp4, &mut list, &mut commands, 0);
 
     spawn_waypoint(wp1, &mut list, &mut commands, 1);
-    // spawn_waypoint(wp2, &mut list, &mut commands, 0);
-    // spawn_waypoint(wp3, &mut list, &mut commands, 0);
     spawn_waypoint(wp4, &mut list, &mut commands, 1);
 
-    let default_shape = shapes::Circle::default();
-    commands.spawn_bundle(GeometryBuilder::build_as(
-        &default_shape,
-        DrawMode::Stroke(StrokeMode::new(Color::WHITE, 2.0)),
-        Transform::from_xyz(0.0, 0.0, FieldZ::AUTO_PATH.0)
-    )).insert(Trajectory::default()).insert(TrajectoryID(0));
-    commands.spawn_bundle(GeometryBuilder::build_as(
-        &default_shape,
-        DrawMode::Stroke(StrokeMode::new(Color::WHITE, 2.0)),
-        Transform::from_xyz(0.0, 0.0, FieldZ::AUTO_PATH.0)
-    )).insert(Trajectory::default()).insert(TrajectoryID(1));
+    spawn_trajectory(&mut commands, 0);
+    spawn_trajectory(&mut commands, 1);
 
     commands.insert_resource(list);
     commands.insert_resource(CursorState::default());
@@ -119,9 +106,10 @@ pub fn spawn_waypoint(waypoint: Waypoint, list: &mut FieldWaypointList, commands
 
     while list.0.len() <= routine_number {
         list.0.push(vec![]);
+        list.2.push(PathConstraints::default());
     }
 
-    if list.0[routine_number].len() == 0 {
+    if list.0[routine_number].is_empty() {
         fill_color = Color::GREEN;
         stroke_color = Color::LIME_GREEN;
     } else {
@@ -167,7 +155,55 @@ pub fn spawn_waypoint(waypoint: Waypoint, list: &mut FieldWaypointList, commands
         idx: list.0[routine_number].len()
     }));
 
-    list.0[routine_number].push(waypoint)
+    list.0[routine_number].push(waypoint);
+    list.2[routine_number].max_velocities.push(None);
+}
+
+// Inserts a waypoint anywhere in a routine. Entities show whichever waypoint is at their index,
+// so the only new entities needed are the ones for the index past the old end.
+pub fn insert_waypoint(waypoint: Waypoint, idx: usize, list: &mut FieldWaypointList, commands: &mut Commands, routine_number: usize) {
+    spawn_waypoint(waypoint, list, commands, routine_number);
+
+    let routine = &mut list.0[routine_number];
+    let last = routine.pop().unwrap();
+    routine.insert(idx, last);
+
+    let max_velocities = &mut list.2[routine_number].max_velocities;
+    let last = max_velocities.pop().unwrap();
+    max_velocities.insert(idx, last);
+}
+
+// Every entity spawned for a routine, see replace_routines
+pub type RoutineEntityFilter = Or<(With<FieldWaypoint>, With<FieldRotationAnchor>, With<TrajectoryID>)>;
+
+// Swaps in a whole new set of routines, respawning every waypoint and trajectory entity.
+// Routines without constraints get the defaults.
+pub fn replace_routines(
+    routines: Vec<Vec<Waypoint>>,
+    constraints: Vec<PathConstraints>,
+    list: &mut FieldWaypointList,
+    commands: &mut Commands,
+    old_entities: impl Iterator<Item = Entity>
+) {
+    for entity in old_entities {
+        commands.entity(entity).despawn();
+    }
+
+    list.0.clear();
+    list.1 = 0;
+    list.2.clear();
+
+    for (routine_number, routine) in routines.into_iter().enumerate() {
+        for waypoint in routine {
+            spawn_waypoint(waypoint, list, commands, routine_number);
+        }
+        if let Some(c) = constraints.get(routine_number) {
+            if c.max_velocities.len() == list.0[routine_number].len() {
+                list.2[routine_number] = c.clone();
+            }
+        }
+        spawn_trajectory(commands, routine_number);
+    }
 }
 
 pub fn path_continuity_updater(mut waypoints: ResMut<FieldWaypointList>) {
@@ -178,15 +214,25 @@ pub fn path_continuity_updater(mut waypoints: ResMut<FieldWaypointList>) {
             Waypoint::Pose(p) => { p.translation }
         };
 
-        let other_path = &mut waypoints.0[idx - 1];
+        let other_path = &waypoints.0[idx - 1];
         let i = other_path.len() - 1;
 
-        other_path[i] = match other_path.last().unwrap() {
+        let corrected = match other_path.last().unwrap() {
             Waypoint::Translation(_) => { Waypoint::Translation(correct_translation) }
             Waypoint::Pose(p) => { Waypoint::Pose(FieldPose::new(
                 correct_translation,
                 p.rotation
             )) }
+        };
+
+        // Only write when needed so the list isn't flagged as changed every frame
+        if other_path[i] != corrected {
+            waypoints.0[idx - 1][i] = corrected;
+        }
+
+        let start_velocity = waypoints.2[idx].start_velocity;
+        if waypoints.2[idx - 1].end_velocity != start_velocity {
+            waypoints.2[idx - 1].end_velocity = start_velocity;
         }
     }
     if idx < waypoints.0.len() - 1 {
@@ -195,14 +241,23 @@ pub fn path_continuity_updater(mut waypoints: ResMut<FieldWaypointList>) {
             Waypoint::Pose(p) => { p.translation }
         };
 
-        let other_path = &mut waypoints.0[idx + 1];
+        let other_path = &waypoints.0[idx + 1];
 
-        other_path[0] = match other_path[0] {
+        let corrected = match other_path[0] {
             Waypoint::Translation(_) => { Waypoint::Translation(correct_translation) }
             Waypoint::Pose(p) => { Waypoint::Pose(FieldPose::new(
                 correct_translation,
                 p.rotation
             )) }
+        };
+
+        if other_path[0] != corrected {
+            waypoints.0[idx + 1][0] = corrected;
+        }
+
+        let end_velocity = waypoints.2[idx].end_velocity;
+        if waypoints.2[idx + 1].start_velocity != end_velocity {
+            waypoints.2[idx + 1].start_velocity = end_velocity;
         }
     }
 }
@@ -212,7 +267,8 @@ pub fn waypoint_updater(
     field: Res<Field>,
     layout: Res<Layout>,
     mut query: Query<(Entity, &FieldWaypoint, &mut Transform, &mut Visibility)>,
-    waypoints: Res<FieldWaypointList>
+    waypoints: Res<FieldWaypointList>,
+    cursor_state: Res<CursorState>
 ) {
     for i in query.iter_mut() {
         let (entity, field_waypoint, mut transform, mut visibility): (Entity, &FieldWaypoint, Mut<Transform>, Mut<Visibility>) = i;
@@ -243,6 +299,9 @@ pub fn waypoint_updater(
             &pose,
             FieldZ::AUTO_WAYPOINTS.0,
         );
+        if cursor_state.selected == Some(field_waypoint.0) {
+            transform.scale = Vec3::splat(1.3);
+        }
         *visibility = Visibility {
             is_visible: true
         };
@@ -308,27 +367,60 @@ pub fn rotation_anchor_updater(
 #[derive(Debug, Default)]
 pub struct CursorState {
     pos: Option<FieldPosition>,
-    grabbed: CursorGrabOption
+    grabbed: CursorGrabOption,
+    // The grabbed waypoint as it was before the drag, the whole drag is one undo step
+    grab_start: Option<Waypoint>,
+    // Insert of a waypoint that was dragged straight away, undone together with the drag
+    grab_insert: Option<Edit>,
+    // Last waypoint grabbed, the arrow keys nudge it
+    selected: Option<FieldWaypointID>,
+    // Clicks on the field only edit waypoints with the waypoint tool
+    pub tool: CursorTool
+}
+
+// What a click on the field does, the zone tools go back to waypoints once their zone is drawn
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum CursorTool {
+    #[default]
+    Waypoints,
+    ZoneRectangle,
+    ZonePolygon
+}
+
+impl CursorState {
+    pub fn pos(&self) -> Option<FieldPosition> {
+        self.pos
+    }
+
+    pub fn is_grabbing(&self) -> bool {
+        !matches!(self.grabbed, CursorGrabOption::None)
+    }
+
+    // Path and index of the selected waypoint, if it is still on the active path
+    pub fn selected(&self, list: &FieldWaypointList) -> Option<(usize, usize)> {
+        match self.selected {
+            Some(id) if is_active(id, list) && id.idx < list.0[id.path_id].len() => Some((id.path_id, id.idx)),
+            _ => None
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum CursorGrabOption {
     Position(FieldWaypointID),
     Rotation(FieldWaypointID),
+    #[default]
     None
 }
 
-impl Default for CursorGrabOption {
-    fn default() -> Self { CursorGrabOption::None }
-}
-
 pub fn waypoint_grab_system(
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
     mut cursor_moved_events: EventReader<CursorMoved>,
     mut cursor_state: ResMut<CursorState>,
-    layout: Res<Layout>,
-    field: Res<Field>,
-    mut waypoints: ResMut<FieldWaypointList>
+    (field, layout): (Res<Field>, Res<Layout>),
+    snapping: Res<Snapping>,
+    mut waypoints: ResMut<FieldWaypointList>,
+    mut history: ResMut<EditHistory>
 ) {
     for event in cursor_moved_events.iter() {
         let event: &CursorMoved = event;
@@ -344,6 +436,7 @@ pub fn waypoint_grab_system(
         if let Some(new_cursor_pos) = cursor_state.pos {
             match cursor_state.grabbed {
                 CursorGrabOption::Position(id) => {
+                    let new_cursor_pos = snapping.snap_position(new_cursor_pos, to_length(WAYPOINT_RADIUS, &field, &layout));
                     match waypoints.0[id.path_id][id.idx] {
                         Waypoint::Translation(_) => { waypoints.0[id.path_id][id.idx] = Waypoint::Translation(new_cursor_pos) }
                         Waypoint::Pose(pose) => { waypoints.0[id.path_id][id.idx] = Waypoint::Pose(FieldPose::new(new_cursor_pos, pose.rotation)) }
@@ -354,11 +447,11 @@ pub fn waypoint_grab_system(
                         if let Some(cursor_pos) = cursor_state.pos {
                             waypoints.0[id.path_id][id.idx] = Waypoint::Pose(FieldPose::new(
                                 pose.translation,
-                                Angle::new::<radian>(
+                                snapping.snap_heading(Angle::new::<radian>(
                                     (cursor_pos.y - pose.translation.y).get::<meter>().atan2(
                                         (cursor_pos.x - pose.translation.x).get::<meter>()
                                     )
-                                )
+                                ))
                             ));
                         }
                     }
@@ -374,61 +467,251 @@ pub fn waypoint_grab_system(
         if event.button == MouseButton::Left {
             match event.state {
                 ElementState::Pressed => {
-                    if let CursorGrabOption::None = cursor_state.grabbed {
-                        if let Some(mouse_pos) = cursor_state.pos {
-                            'outer: for (path_id, path) in waypoints.0.iter().enumerate() {
-                                for (idx, w) in path.iter().enumerate() {
-                                    let id = FieldWaypointID { path_id, idx };
-
-                                    if !is_active(id, &waypoints) {
-                                        continue;
-                                    }
-
-                                    let field_position: &FieldPosition;
-
-                                    match w {
-                                        Waypoint::Pose(pose) => {
-                                            field_position = &pose.translation;
-
-                                            let theta = pose.rotation.get::<radian>();
-                                            let anchor_pos = FieldPosition::new(
-                                                field_position.x + (
-                                                    theta.cos() * ROTATION_ANCHOR_REVOLUTION_RADIUS *
-                                                        (field.size.x / layout.field.size.x)
-                                                ),
-                                                field_position.y + (
-                                                    theta.sin() * ROTATION_ANCHOR_REVOLUTION_RADIUS *
-                                                        (field.size.y / layout.field.size.y)
-                                                )
-                                            );
-
-                                            let d = mouse_pos.dist(&anchor_pos);
-                                            let d = layout.field.size.x * d.get::<meter>() / field.size.x.get::<meter>();
-
-                                            if d <= ROTATION_ANCHOR_POINT_RADIUS {
-                                                cursor_state.grabbed = CursorGrabOption::Rotation(id);
-                                                break 'outer;
-                                            }
-                                        }
-                                        Waypoint::Translation(t) => { field_position = t }
-                                    }
-
-                                    let d = mouse_pos.dist(field_position);
-                                    let d = layout.field.size.x * d.get::<meter>() / field.size.x.get::<meter>();
-
-                                    if d <= WAYPOINT_RADIUS {
-                                        cursor_state.grabbed = CursorGrabOption::Position(id);
-                                        break;
-                                    }
-                                }
-                            }
+                    if cursor_state.tool != CursorTool::Waypoints {
+                        continue;
+                    }
+                    if let (CursorGrabOption::None, Some(mouse_pos)) = (&cursor_state.grabbed, cursor_state.pos) {
+                        let grabbed = waypoint_at(&mouse_pos, &waypoints, &field, &layout);
+                        if let CursorGrabOption::Position(id) | CursorGrabOption::Rotation(id) = grabbed {
+                            cursor_state.grab_start = Some(waypoints.0[id.path_id][id.idx]);
+                            cursor_state.selected = Some(id);
                         }
+                        cursor_state.grabbed = grabbed;
                     }
                 }
                 ElementState::Released => {
+                    let id = match cursor_state.grabbed {
+                        CursorGrabOption::Position(id) | CursorGrabOption::Rotation(id) => Some(id),
+                        CursorGrabOption::None => None
+                    };
+
+                    let moved = match (id, cursor_state.grab_start) {
+                        (Some(id), Some(before)) if waypoints.0[id.path_id][id.idx] != before => {
+                            Some(Edit::Set { path_id: id.path_id, idx: id.idx, before, after: waypoints.0[id.path_id][id.idx] })
+                        }
+                        _ => None
+                    };
+
+                    match (cursor_state.grab_insert.take(), moved) {
+                        (Some(insert), Some(moved)) => history.record(Edit::Group(vec![insert, moved])),
+                        (Some(edit), None) | (None, Some(edit)) => history.record(edit),
+                        (None, None) => {}
+                    }
+
                     cursor_state.grabbed = CursorGrabOption::None;
+                    cursor_state.grab_start = None;
+                }
+            }
+        }
+    }
+}
+
+fn translation(waypoint: &Waypoint) -> FieldPosition {
+    match waypoint {
+        Waypoint::Translation(t) => { *t }
+        Waypoint::Pose(p) => { p.translation }
+    }
+}
+
+fn to_pixels(d: Length, field: &Field, layout: &Layout) -> f32 {
+    layout.field.size.x * d.get::<meter>() / field.size.x.get::<meter>()
+}
+
+fn to_length(pixels: f32, field: &Field, layout: &Layout) -> Length {
+    Length::new::<meter>(pixels * field.size.x.get::<meter>() / layout.field.size.x)
+}
+
+// The waypoint or rotation anchor of the active path under the cursor
+fn waypoint_at(mouse_pos: &FieldPosition, waypoints: &FieldWaypointList, field: &Field, layout: &Layout) -> CursorGrabOption {
+    for (path_id, path) in waypoints.0.iter().enumerate() {
+        for (idx, w) in path.iter().enumerate() {
+            let id = FieldWaypointID { path_id, idx };
+
+            if !is_active(id, waypoints) {
+                continue;
+            }
+
+            if let Waypoint::Pose(pose) = w {
+                let theta = pose.rotation.get::<radian>();
+                let anchor_pos = FieldPosition::new(
+                    pose.translation.x + (
+                        theta.cos() * ROTATION_ANCHOR_REVOLUTION_RADIUS *
+                            (field.size.x / layout.field.size.x)
+                    ),
+                    pose.translation.y + (
+                        theta.sin() * ROTATION_ANCHOR_REVOLUTION_RADIUS *
+                            (field.size.y / layout.field.size.y)
+                    )
+                );
+
+                if to_pixels(mouse_pos.dist(&anchor_pos), field, layout) <= ROTATION_ANCHOR_POINT_RADIUS {
+                    return CursorGrabOption::Rotation(id);
                 }
             }
+
+            if to_pixels(mouse_pos.dist(&translation(w)), field, layout) <= WAYPOINT_RADIUS {
+                return CursorGrabOption::Position(id);
+            }
+        }
+    }
+    CursorGrabOption::None
+}
+
+fn nearest_state(states: &[TrajectoryState], pos: &FieldPosition) -> Option<usize> {
+    states.iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.pose.translation.dist(pos).get::<meter>().total_cmp(&b.pose.translation.dist(pos).get::<meter>()))
+        .map(|(i, _)| i)
+}
+
+// Where a click on the drawn trajectory puts a new waypoint, as the index it is inserted at and the point on the path
+fn insertion_point(mouse_pos: &FieldPosition, trajectory: &TimedTrajectory, path: &[Waypoint], field: &Field, layout: &Layout) -> Option<(usize, FieldPosition)> {
+    let clicked = nearest_state(&trajectory.0, mouse_pos)?;
+    let position = trajectory.0[clicked].pose.translation;
+    if to_pixels(mouse_pos.dist(&position), field, layout) > WAYPOINT_RADIUS {
+        return None;
+    }
+
+    // The trajectory passes through the waypoints in order, so search for each one past the previous
+    let mut start = 0;
+    for (idx, w) in path.iter().enumerate().skip(1) {
+        start += nearest_state(&trajectory.0[start..], &translation(w))?;
+        if start > clicked {
+            return Some((idx, position));
         }
     }
-}
\ No newline at end of file
+    None
+}
+
+// Direction of travel through a waypoint, estimated from its neighbours
+fn heading_through(path: &[Waypoint], idx: usize) -> Angle {
+    let from = translation(&path[idx.saturating_sub(1)]);
+    let to = translation(&path[(idx + 1).min(path.len() - 1)]);
+    Angle::new::<radian>((to.y - from.y).get::<meter>().atan2((to.x - from.x).get::<meter>()))
+}
+
+// Removes a waypoint, an end that gets removed hands its heading to the waypoint that becomes the new end
+fn remove_edit(path: &[Waypoint], constraints: &PathConstraints, id: FieldWaypointID) -> Edit {
+    let removed = path[id.idx];
+    let max_velocity = constraints.max_velocities[id.idx];
+    let mut edits = vec![Edit::Remove { path_id: id.path_id, idx: id.idx, waypoint: removed, max_velocity }];
+
+    let new_end = if id.idx == 0 {
+        Some((0, path[1]))
+    } else if id.idx == path.len() - 1 {
+        Some((id.idx - 1, path[id.idx - 1]))
+    } else {
+        None
+    };
+
+    if let (Some((idx, Waypoint::Translation(t))), Waypoint::Pose(p)) = (new_end, removed) {
+        edits.push(Edit::Set {
+            path_id: id.path_id,
+            idx,
+            before: Waypoint::Translation(t),
+            after: Waypoint::Pose(FieldPose::new(t, p.rotation))
+        });
+    }
+
+    Edit::Group(edits)
+}
+
+// Left click on the active trajectory inserts a waypoint and starts dragging it, right click removes a waypoint
+// and middle click toggles it between a translation and a pose.
+// Runs after the grab system, which takes any left click on a waypoint first.
+pub fn waypoint_edit_system(
+    mut mouse_button_input_events: EventReader<MouseButtonInput>,
+    mut cursor_state: ResMut<CursorState>,
+    (field, layout): (Res<Field>, Res<Layout>),
+    (mut waypoints, mut zones): (ResMut<FieldWaypointList>, ResMut<ConstraintZones>),
+    mut history: ResMut<EditHistory>,
+    trajectories: Query<(&TrajectoryID, &TimedTrajectory)>,
+    mut commands: Commands
+) {
+    for event in mouse_button_input_events.iter() {
+        let event: &MouseButtonInput = event;
+
+        let mouse_pos = match (event.state, cursor_state.pos, &cursor_state.grabbed, cursor_state.tool) {
+            (ElementState::Pressed, Some(pos), CursorGrabOption::None, CursorTool::Waypoints) => pos,
+            _ => continue
+        };
+        let path_id = waypoints.1;
+
+        match event.button {
+            MouseButton::Left => {
+                let trajectory = trajectories.iter().find(|(id, _)| id.0 == path_id).map(|(_, t)| t);
+                let point = trajectory.and_then(|t| insertion_point(&mouse_pos, t, &waypoints.0[path_id], &field, &layout));
+
+                if let Some((idx, position)) = point {
+                    let waypoint = Waypoint::Translation(position);
+                    // Recorded when the drag ends, so the insert and the drag are one undo step
+                    let insert = Edit::Insert { path_id, idx, waypoint, max_velocity: None };
+                    insert.apply(&mut waypoints, &mut zones, &mut commands);
+
+                    cursor_state.grabbed = CursorGrabOption::Position(FieldWaypointID { path_id, idx });
+                    cursor_state.grab_start = Some(waypoint);
+                    cursor_state.grab_insert = Some(insert);
+                    cursor_state.selected = Some(FieldWaypointID { path_id, idx });
+                }
+            }
+            MouseButton::Right => {
+                if let CursorGrabOption::Position(id) | CursorGrabOption::Rotation(id) = waypoint_at(&mouse_pos, &waypoints, &field, &layout) {
+                    // Every path keeps both of its ends
+                    if waypoints.0[path_id].len() > 2 {
+                        let edit = remove_edit(&waypoints.0[path_id], &waypoints.2[path_id], id);
+                        history.perform(edit, &mut waypoints, &mut zones, &mut commands);
+                        cursor_state.selected = None;
+                    }
+                }
+            }
+            MouseButton::Middle => {
+                if let CursorGrabOption::Position(id) | CursorGrabOption::Rotation(id) = waypoint_at(&mouse_pos, &waypoints, &field, &layout) {
+                    let before = waypoints.0[path_id][id.idx];
+                    let after = match before {
+                        Waypoint::Translation(t) => { Waypoint::Pose(FieldPose::new(t, heading_through(&waypoints.0[path_id], id.idx))) }
+                        Waypoint::Pose(p) => { Waypoint::Translation(p.translation) }
+                    };
+                    history.perform(Edit::Set { path_id, idx: id.idx, before, after }, &mut waypoints, &mut zones, &mut commands);
+                }
+            }
+            MouseButton::Other(_) => {}
+        }
+    }
+}
+
+// Arrow keys move the selected waypoint by one nudge step, each press is its own undo step
+pub fn waypoint_nudge_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    cursor_state: Res<CursorState>,
+    snapping: Res<Snapping>,
+    (mut waypoints, mut zones): (ResMut<FieldWaypointList>, ResMut<ConstraintZones>),
+    mut history: ResMut<EditHistory>,
+    mut commands: Commands
+) {
+    let id = match (cursor_state.selected, &cursor_state.grabbed) {
+        (Some(id), CursorGrabOption::None) if is_active(id, &waypoints) && id.idx < waypoints.0[id.path_id].len() => id,
+        _ => return
+    };
+
+    let step = snapping.nudge_step();
+    let mut offset = (Length::ZERO, Length::ZERO);
+    if keyboard_input.just_pressed(KeyCode::Left) { offset.0 -= step; }
+    if keyboard_input.just_pressed(KeyCode::Right) { offset.0 += step; }
+    if keyboard_input.just_pressed(KeyCode::Down) { offset.1 -= step; }
+    if keyboard_input.just_pressed(KeyCode::Up) { offset.1 += step; }
+    if offset == (Length::ZERO, Length::ZERO) {
+        return;
+    }
+
+    let before = waypoints.0[id.path_id][id.idx];
+    let position = translation(&before);
+    let position = snapping.snap_to_grid(FieldPosition::new(position.x + offset.0, position.y + offset.1));
+    let after = match before {
+        Waypoint::Translation(_) => { Waypoint::Translation(position) }
+        Waypoint::Pose(p) => { Waypoint::Pose(FieldPose::new(position, p.rotation)) }
+    };
+
+    if after != before {
+        history.perform(Edit::Set { path_id: id.path_id, idx: id.idx, before, after }, &mut waypoints, &mut zones, &mut commands);
+    }
+}
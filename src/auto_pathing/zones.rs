@@ -0,0 +1,285 @@
+use bevy::input::ElementState;
+use bevy::input::mouse::MouseButtonInput;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use serde::{Deserialize, Serialize};
+use uom::ConstZero;
+use uom::si::f32::{Acceleration, Angle, Length, Velocity};
+use uom::si::length::meter;
+use uom::si::velocity::meter_per_second;
+
+use crate::auto_pathing::history::{Edit, EditHistory};
+use crate::auto_pathing::waypoints::{CursorState, CursorTool, FieldWaypointList};
+use crate::field::FieldPosition;
+use crate::field::render::FieldZ;
+use crate::field::shapes::FieldPath;
+
+// Speed cap a newly drawn zone starts with
+const DEFAULT_ZONE_VELOCITY: f32 = 1.0;
+// Rectangles smaller than this on either side are treated as a stray click
+const MIN_ZONE_SIZE_M: f32 = 0.05;
+// Clicking this close to the first corner closes a polygon
+const CLOSE_POLYGON_DISTANCE_M: f32 = 0.2;
+
+const ZONE_FILL: Color = Color::rgba(1.0, 0.55, 0.0, 0.2);
+const ZONE_OUTLINE: Color = Color::ORANGE;
+const SELECTED_ZONE_OUTLINE: Color = Color::YELLOW;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ZoneShape {
+    Rectangle { min: FieldPosition, max: FieldPosition },
+    // Corners in order, the last one connects back to the first
+    Polygon(Vec<FieldPosition>),
+}
+
+// Caps the velocity and acceleration of every trajectory while it is inside the zone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConstraintZone {
+    pub shape: ZoneShape,
+    pub max_velocity: Option<Velocity>,
+    pub max_acceleration: Option<Acceleration>,
+}
+
+// Zones apply to every routine, so they live outside FieldWaypointList
+#[derive(Default)]
+pub struct ConstraintZones(pub Vec<ConstraintZone>);
+
+// Zone the config panel edits and the one being drawn
+#[derive(Default)]
+pub struct ZoneEditor {
+    pub selected: Option<usize>,
+    drag_start: Option<FieldPosition>,
+    polygon: Vec<FieldPosition>,
+    // Zone being drawn, up to the cursor
+    preview: Option<ZoneShape>,
+}
+
+// Index of the zone an overlay draws, None for the zone being drawn
+#[derive(Component)]
+pub struct ZoneOverlay(Option<usize>);
+
+impl ZoneShape {
+    // Built from two opposite corners in any order
+    fn rectangle(a: FieldPosition, b: FieldPosition) -> Self {
+        ZoneShape::Rectangle {
+            min: FieldPosition::new(a.x.min(b.x), a.y.min(b.y)),
+            max: FieldPosition::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+
+    pub fn vertices(&self) -> Vec<FieldPosition> {
+        match self {
+            ZoneShape::Rectangle { min, max } => vec![
+                *min,
+                FieldPosition::new(max.x, min.y),
+                *max,
+                FieldPosition::new(min.x, max.y),
+            ],
+            ZoneShape::Polygon(points) => points.clone(),
+        }
+    }
+
+    // Even-odd rule, so polygons work whichever way they wind
+    pub fn contains(&self, p: &FieldPosition) -> bool {
+        if let ZoneShape::Rectangle { min, max } = self {
+            return p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y;
+        }
+
+        let vertices: Vec<(f32, f32)> = self.vertices().iter().map(|v| (v.x.get::<meter>(), v.y.get::<meter>())).collect();
+        let (px, py) = (p.x.get::<meter>(), p.y.get::<meter>());
+
+        let mut inside = false;
+        for (i, (xi, yi)) in vertices.iter().enumerate() {
+            let (xj, yj) = vertices[(i + vertices.len() - 1) % vertices.len()];
+            if (*yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+}
+
+impl ConstraintZone {
+    fn new(shape: ZoneShape) -> Self {
+        Self {
+            shape,
+            max_velocity: Some(Velocity::new::<meter_per_second>(DEFAULT_ZONE_VELOCITY)),
+            max_acceleration: None,
+        }
+    }
+}
+
+impl ZoneEditor {
+    pub fn cancel(&mut self) {
+        self.drag_start = None;
+        self.polygon.clear();
+        self.preview = None;
+    }
+}
+
+// Rectangles are drawn as paths too, so an overlay can show any zone that ends up at its index
+fn overlay_path(shape: &ZoneShape) -> FieldPath {
+    // FieldPath points are offsets from the previous corner
+    let vertices = shape.vertices();
+    FieldPath {
+        origin: vertices[0],
+        points: vertices.windows(2).map(|w| FieldPosition::new(w[1].x - w[0].x, w[1].y - w[0].y)).collect(),
+        rotation: Angle::ZERO,
+    }
+}
+
+fn overlay_draw_mode(outline: Color) -> DrawMode {
+    DrawMode::Outlined {
+        fill_mode: FillMode::color(ZONE_FILL),
+        outline_mode: StrokeMode::new(outline, 2.0),
+    }
+}
+
+// Overlays are kept per zone index and updated in place, field_path_updater rebuilds the ones whose shape changed
+pub fn zone_overlay_updater(
+    mut commands: Commands,
+    zones: Res<ConstraintZones>,
+    editor: Res<ZoneEditor>,
+    mut overlays: Query<(Entity, &ZoneOverlay, &mut FieldPath, &mut DrawMode)>
+) {
+    if !(zones.is_changed() || editor.is_changed()) {
+        return;
+    }
+
+    let shape = |idx: Option<usize>| match idx {
+        Some(idx) => zones.0.get(idx).map(|z| &z.shape),
+        None => editor.preview.as_ref()
+    };
+    let outline = |idx: Option<usize>| match idx {
+        Some(_) if editor.selected != idx => ZONE_OUTLINE,
+        _ => SELECTED_ZONE_OUTLINE
+    };
+
+    let mut shown: Vec<Option<usize>> = Vec::with_capacity(zones.0.len() + 1);
+    for i in overlays.iter_mut() {
+        let (entity, overlay, mut path, mut draw_mode): (Entity, &ZoneOverlay, Mut<FieldPath>, Mut<DrawMode>) = i;
+
+        let shape = match shape(overlay.0) {
+            Some(shape) if !shown.contains(&overlay.0) => shape,
+            _ => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        shown.push(overlay.0);
+
+        let new_path = overlay_path(shape);
+        if *path != new_path {
+            *path = new_path;
+        }
+        let new_draw_mode = overlay_draw_mode(outline(overlay.0));
+        if *draw_mode != new_draw_mode {
+            *draw_mode = new_draw_mode;
+        }
+    }
+
+    for idx in (0..zones.0.len()).map(Some).chain(std::iter::once(None)) {
+        if let (Some(shape), false) = (shape(idx), shown.contains(&idx)) {
+            commands.spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Rectangle::default(),
+                overlay_draw_mode(outline(idx)),
+                Transform::default()
+            ))
+                .insert(overlay_path(shape))
+                .insert(FieldZ::CONSTRAINT_ZONES)
+                .insert(ZoneOverlay(idx));
+        }
+    }
+}
+
+// Rectangles are dragged out, polygons are clicked corner by corner and closed by clicking the first corner or
+// right clicking. With the waypoint tool a click inside a zone selects it.
+// Runs after the waypoint systems so a click they used, or the right click closing a polygon, isn't seen twice.
+pub fn zone_draw_system(
+    mut mouse_button_input_events: EventReader<MouseButtonInput>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut cursor_state: ResMut<CursorState>,
+    mut editor: ResMut<ZoneEditor>,
+    (mut zones, mut list): (ResMut<ConstraintZones>, ResMut<FieldWaypointList>),
+    mut history: ResMut<EditHistory>,
+    mut commands: Commands
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) && cursor_state.tool != CursorTool::Waypoints {
+        editor.cancel();
+        cursor_state.tool = CursorTool::Waypoints;
+    }
+
+    for event in mouse_button_input_events.iter() {
+        let event: &MouseButtonInput = event;
+
+        let pos = match cursor_state.pos() {
+            Some(pos) => pos,
+            None => continue
+        };
+
+        let finished = match (cursor_state.tool, event.button, event.state) {
+            (CursorTool::Waypoints, MouseButton::Left, ElementState::Pressed) => {
+                if !cursor_state.is_grabbing() {
+                    // The zone drawn last is on top
+                    let selected = zones.0.iter().rposition(|z| z.shape.contains(&pos));
+                    if editor.selected != selected {
+                        editor.selected = selected;
+                    }
+                }
+                None
+            }
+            (CursorTool::ZoneRectangle, MouseButton::Left, ElementState::Pressed) => {
+                editor.drag_start = Some(pos);
+                None
+            }
+            (CursorTool::ZoneRectangle, MouseButton::Left, ElementState::Released) => {
+                let min_size = Length::new::<meter>(MIN_ZONE_SIZE_M);
+                match editor.drag_start {
+                    Some(start) if (pos.x - start.x).abs() >= min_size && (pos.y - start.y).abs() >= min_size => {
+                        Some(ZoneShape::rectangle(start, pos))
+                    }
+                    Some(_) => {
+                        editor.cancel();
+                        None
+                    }
+                    None => None
+                }
+            }
+            (CursorTool::ZonePolygon, MouseButton::Left, ElementState::Pressed) => {
+                let closes = editor.polygon.len() >= 3
+                    && editor.polygon[0].dist(&pos) <= Length::new::<meter>(CLOSE_POLYGON_DISTANCE_M);
+                if closes {
+                    Some(ZoneShape::Polygon(editor.polygon.clone()))
+                } else {
+                    editor.polygon.push(pos);
+                    None
+                }
+            }
+            (CursorTool::ZonePolygon, MouseButton::Right, ElementState::Pressed) if editor.polygon.len() >= 3 => {
+                Some(ZoneShape::Polygon(editor.polygon.clone()))
+            }
+            _ => None
+        };
+
+        // A finished zone is selected so its limits can be set straight away
+        if let Some(shape) = finished {
+            let idx = zones.0.len();
+            history.perform(Edit::AddZone { idx, zone: ConstraintZone::new(shape) }, &mut list, &mut zones, &mut commands);
+            editor.selected = Some(idx);
+            editor.cancel();
+            cursor_state.tool = CursorTool::Waypoints;
+        }
+    }
+
+    let preview = match (cursor_state.tool, cursor_state.pos()) {
+        (CursorTool::ZoneRectangle, Some(pos)) => editor.drag_start.map(|start| ZoneShape::rectangle(start, pos)),
+        (CursorTool::ZonePolygon, Some(pos)) if !editor.polygon.is_empty() => {
+            Some(ZoneShape::Polygon(editor.polygon.iter().copied().chain(std::iter::once(pos)).collect()))
+        }
+        _ => None
+    };
+    // Only written when it differs, the overlays are updated on every change
+    if editor.preview != preview {
+        editor.preview = preview;
+    }
+}
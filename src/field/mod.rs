@@ -2,6 +2,7 @@ pub mod objects;
 pub mod render;
 pub mod shapes;
 
+use std::f32::consts::PI;
 use bevy::prelude::*;
 use uom::num_traits::Pow;
 
@@ -11,7 +12,7 @@ use uom::si::length::meter;
 
 use crate::layout::Layout;
 
-use serde::{Serialize, Deserialize, Serializer};
+use serde::{Serialize, Deserialize};
 
 pub struct FieldManagementPlugin;
 
@@ -19,13 +20,13 @@ pub struct Field {
     pub size: FieldPosition,
 }
 
-#[derive(Default, Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldPosition {
     pub x: Length,
     pub y: Length,
 }
 
-#[derive(Component, Default, Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Component, Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldPose {
     pub translation: FieldPosition,
     pub rotation: Angle,
@@ -62,12 +63,12 @@ impl Field {
     }
 
     pub fn to_screen_transform(&self, layout: &Layout, pose: &FieldPose, z: f32) -> Transform {
-        Transform::from_translation(self.to_screen_vec(&layout, &pose.translation).extend(z))
+        Transform::from_translation(self.to_screen_vec(layout, &pose.translation).extend(z))
             .with_rotation(Quat::from_rotation_z(pose.rotation.get::<radian>()))
     }
 
     pub fn to_field_position(&self, layout: &Layout, pos: Vec2) -> Option<FieldPosition> {
-        return if pos.x < layout.field.pos.x
+        if pos.x < layout.field.pos.x
             || pos.y < layout.field.pos.y
             || pos.x > layout.field.pos.x + layout.field.size.x
             || pos.y > layout.field.pos.y + layout.field.size.y
@@ -98,6 +99,30 @@ impl FieldPose {
     pub fn new(pos: FieldPosition, rotation: Angle) -> Self {
         Self { translation: pos, rotation }
     }
+
+    // Interpolates translation linearly and rotation along the shortest arc, t in [0, 1]
+    pub fn interpolate(&self, other: &FieldPose, t: f32) -> FieldPose {
+        let a = self.rotation.get::<radian>();
+        let diff = wrap_angle(other.rotation.get::<radian>() - a);
+
+        FieldPose::new(
+            FieldPosition::new(
+                self.translation.x + (other.translation.x - self.translation.x) * t,
+                self.translation.y + (other.translation.y - self.translation.y) * t,
+            ),
+            Angle::new::<radian>(a + diff * t)
+        )
+    }
+}
+
+// Wraps x to [-range / 2, range / 2)
+pub fn wrap(x: f32, range: f32) -> f32 {
+    (x + range / 2.0).rem_euclid(range) - range / 2.0
+}
+
+// Wraps an angle in radians to [-PI, PI)
+pub fn wrap_angle(a: f32) -> f32 {
+    wrap(a, 2.0 * PI)
 }
 
 impl Default for Field {
@@ -11,6 +11,9 @@ use crate::field::{Field, FieldPose, FieldPosition};
 use crate::field::render::FieldZ;
 use crate::field::shapes::{FieldCircle, FieldPath};
 
+#[derive(Component)]
+pub struct Hub;
+
 pub fn spawn_objects(mut commands: Commands) {
     let default_shape = shapes::Circle::default();
 
@@ -24,7 +27,7 @@ pub fn spawn_objects(mut commands: Commands) {
         rotation: Angle::new::<radian>(0.0)
     }).insert(
         FieldCircle(Length::new::<foot>(2.0))
-    ).insert(FieldZ::FIELD_OBJECTS);
+    ).insert(FieldZ::FIELD_OBJECTS).insert(Hub);
 
     let small_offset: f32 = (237.31_f32 / 2.0).pow(2) - (219.25_f32 / 2.0).pow(2);
     let small_offset = small_offset.sqrt();
@@ -51,7 +54,7 @@ pub fn spawn_objects(mut commands: Commands) {
 }
 
 fn spawn_tarmac(commands: &mut Commands, origin: FieldPosition, rotation: Angle, color: Color) {
-    let tape_path = vec![
+    let tape_path = [
         (82.83, 0.0),
         (-75.07 * 67.5_f32.to_radians().cos(), 75.07 * 67.5_f32.to_radians().sin()),
         (-46.89 * (135.0_f32 - (90.0 - 67.5)).to_radians().sin(), -46.89 * (135.0_f32 - (90.0 - 67.5)).to_radians().cos()),
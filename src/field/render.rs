@@ -14,9 +14,11 @@ pub struct FieldZ(pub f32);
 
 impl FieldZ {
     pub const FIELD_OBJECTS: FieldZ = FieldZ(0.0);
+    pub const CONSTRAINT_ZONES: FieldZ = FieldZ(0.5);
     pub const AUTO_PATH: FieldZ = FieldZ(1.0);
     pub const AUTO_WAYPOINTS: FieldZ = FieldZ(2.0);
     pub const ROBOT: FieldZ = FieldZ(3.0);
+    pub const ODOMETRY: FieldZ = FieldZ(4.0);
 }
 
 // Updates the position and rotation of field-relative sprites to reflect their pose
@@ -29,7 +31,7 @@ pub fn field_pose_updater(
         let (pose, mut transform, z): (&FieldPose, Mut<Transform>, Option<&FieldZ>) = i;
         *transform = field.to_screen_transform(
             &layout,
-            &pose,
+            pose,
             match z {
                 None => 0_f32,
                 Some(field_z) => field_z.0,
@@ -69,38 +71,39 @@ pub fn field_circle_updater(
     };
 }
 
+// Shapes spawned or changed after startup are built straight away instead of waiting for a layout change
 pub fn field_rect_updater(
     field: Res<Field>,
+    layout: Res<Layout>,
     layout_changed_event: Res<Events<LayoutChangedEvent>>,
-    mut query: Query<(&FieldRectangle, &mut Path)>,
+    mut query: Query<(ChangeTrackers<FieldRectangle>, &FieldRectangle, &mut Path)>,
 ) {
-    match layout_changed_event
+    let layout_changed = layout_changed_event
         .get_reader()
         .iter(&layout_changed_event)
         .next_back()
-    {
-        None => {}
-        Some(e) => {
-            let layout: &Layout = &e.0;
+        .is_some();
 
-            for i in query.iter_mut() {
-                let (r, mut path): (&FieldRectangle, Mut<Path>) = i;
-                let shape = shapes::Rectangle {
-                    extents: Vec2::new(
-                        layout.field.size.x * r.width.get::<meter>() / field.size.x.get::<meter>(),
-                        layout.field.size.x * r.height.get::<meter>() / field.size.x.get::<meter>(),
-                    ),
-                    origin: r.origin,
-                };
-                let geometry = GeometryBuilder::build_as(
-                    &shape,
-                    DrawMode::Fill(FillMode::color(Color::default())),
-                    Default::default(),
-                );
-                *path = geometry.path;
-            }
+    for i in query.iter_mut() {
+        let (tracker, r, mut path): (ChangeTrackers<FieldRectangle>, &FieldRectangle, Mut<Path>) = i;
+        if !(layout_changed || tracker.is_changed()) {
+            continue;
         }
-    };
+
+        let shape = shapes::Rectangle {
+            extents: Vec2::new(
+                layout.field.size.x * r.width.get::<meter>() / field.size.x.get::<meter>(),
+                layout.field.size.x * r.height.get::<meter>() / field.size.x.get::<meter>(),
+            ),
+            origin: r.origin,
+        };
+        let geometry = GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Fill(FillMode::color(Color::default())),
+            Default::default(),
+        );
+        *path = geometry.path;
+    }
 }
 
 fn build_field_path(path: &FieldPath, field: &Field, layout: &Layout) -> Path {
@@ -124,40 +127,42 @@ fn build_field_path(path: &FieldPath, field: &Field, layout: &Layout) -> Path {
     builder.build()
 }
 
+type FieldPathItem<'a> = (ChangeTrackers<FieldPath>, &'a FieldPath, &'a mut Path, &'a mut Transform, Option<&'a FieldZ>);
+
 pub fn field_path_updater(
     field: Res<Field>,
+    layout: Res<Layout>,
     layout_changed_event: Res<Events<LayoutChangedEvent>>,
-    mut query: Query<(&FieldPath, &mut Path, &mut Transform, Option<&FieldZ>)>,
+    mut query: Query<FieldPathItem>,
 ) {
-    match layout_changed_event
+    let layout_changed = layout_changed_event
         .get_reader()
         .iter(&layout_changed_event)
         .next_back()
-    {
-        None => {}
-        Some(e) => {
-            let layout: &Layout = &e.0;
+        .is_some();
 
-            for i in query.iter_mut() {
-                let (p, mut path, mut transform, z): (
-                    &FieldPath,
-                    Mut<Path>,
-                    Mut<Transform>,
-                    Option<&FieldZ>,
-                ) = i;
-
-                let z = match z {
-                    None => 0_f32,
-                    Some(field_z) => field_z.0,
-                };
+    for i in query.iter_mut() {
+        let (tracker, p, mut path, mut transform, z): (
+            ChangeTrackers<FieldPath>,
+            &FieldPath,
+            Mut<Path>,
+            Mut<Transform>,
+            Option<&FieldZ>,
+        ) = i;
+        if !(layout_changed || tracker.is_changed()) {
+            continue;
+        }
 
-                *path = build_field_path(p, &field, layout);
-                *transform = Transform {
-                    translation: field.to_screen_vec(&layout, &p.origin).extend(z),
-                    rotation: Quat::from_rotation_z(p.rotation.get::<radian>()),
-                    ..*transform
-                }
-            }
+        let z = match z {
+            None => 0_f32,
+            Some(field_z) => field_z.0,
+        };
+
+        *path = build_field_path(p, &field, &layout);
+        *transform = Transform {
+            translation: field.to_screen_vec(&layout, &p.origin).extend(z),
+            rotation: Quat::from_rotation_z(p.rotation.get::<radian>()),
+            ..*transform
         }
-    };
+    }
 }
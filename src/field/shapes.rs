@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 
 use crate::field::FieldPosition;
+use uom::si::angle::radian;
 use uom::si::f32::{Angle, Length};
 
 #[derive(Component)]
@@ -14,9 +15,29 @@ pub struct FieldRectangle {
     pub origin: RectangleOrigin,
 }
 
-#[derive(Default, Component)]
+#[derive(Default, Component, PartialEq)]
 pub struct FieldPath {
     pub origin: FieldPosition,
     pub points: Vec<FieldPosition>,
     pub rotation: Angle,
 }
+
+impl FieldPath {
+    // Corners in field coordinates, the points are offsets from the previous corner rotated about the origin
+    pub fn vertices(&self) -> Vec<FieldPosition> {
+        let (sin, cos) = self.rotation.get::<radian>().sin_cos();
+        let mut cum_x = Length::default();
+        let mut cum_y = Length::default();
+
+        let mut vertices = vec![self.origin];
+        for p in &self.points {
+            cum_x += p.x;
+            cum_y += p.y;
+            vertices.push(FieldPosition::new(
+                self.origin.x + cum_x * cos - cum_y * sin,
+                self.origin.y + cum_x * sin + cum_y * cos
+            ));
+        }
+        vertices
+    }
+}
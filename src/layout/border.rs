@@ -17,7 +17,7 @@ pub fn add_borders(mut commands: Commands, layout: Res<Layout>, asset_server: Re
 
 pub fn generate_border(commands: &mut Commands, layout: &Layout, location: fn(&Layout) -> &LayoutRect, name: &str, color: Color, asset_server: &AssetServer) {
     commands.spawn_bundle(
-        render::get_border_geometry(location(layout), color.clone())
+        render::get_border_geometry(location(layout), color)
     ).insert(BorderElement {
         layout_rect: location,
         color
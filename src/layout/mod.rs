@@ -1,6 +1,7 @@
 pub mod border;
 pub mod event;
 pub mod render;
+pub mod ui;
 
 use bevy::prelude::*;
 
@@ -32,15 +33,19 @@ pub struct LayoutRect {
 impl Plugin for LayoutPlugin {
     fn build(&self, app: &mut App) {
         app.world
-            .get_resource_or_insert_with(|| LayoutSettings::default());
+            .get_resource_or_insert_with(LayoutSettings::default);
         app.insert_resource(Layout::default());
 
         app.add_event::<LayoutChangedEvent>();
 
         app.add_startup_system(border::add_borders);
+        app.add_startup_system(ui::setup);
         app.add_system(render::update_border);
         app.add_system(render::update_border_text);
         app.add_system(event::layout_event_update);
+        app.add_system(ui::panel_root_updater);
+        app.add_system(ui::console_row_updater);
+        app.add_system(ui::button_color_system);
     }
 }
 
@@ -59,7 +59,7 @@ pub fn update_border(mut query: Query<(&BorderElement, &mut Path, &mut Transform
 
             for i in query.iter_mut() {
                 let (element, mut path, mut transform, mut mode): (&BorderElement, Mut<Path>, Mut<Transform>, Mut<DrawMode>) = i;
-                let geometry = get_border_geometry((element.layout_rect)(layout), element.color.clone());
+                let geometry = get_border_geometry((element.layout_rect)(layout), element.color);
                 *path = geometry.path;
                 *transform = geometry.transform;
                 *mode = geometry.mode;
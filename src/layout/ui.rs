@@ -0,0 +1,173 @@
+use bevy::app::Events;
+use bevy::prelude::*;
+
+use crate::Layout;
+use crate::layout::LayoutRect;
+use crate::layout::event::LayoutChangedEvent;
+use crate::layout::render::FONT_SIZE;
+
+pub const NORMAL_BUTTON: Color = Color::rgb(0.35, 0.35, 0.35);
+pub const HOVERED_BUTTON: Color = Color::rgb(0.45, 0.45, 0.45);
+pub const PRESSED_BUTTON: Color = Color::rgb(0.55, 0.75, 0.55);
+pub const TEXT_COLOR: Color = Color::GRAY;
+pub const BUTTON_TEXT_COLOR: Color = Color::BLACK;
+
+// Status lines and the compact console rows
+pub const SMALL_FONT_SIZE: f32 = FONT_SIZE * 0.6;
+
+// Column of widgets filling a layout rect below its border title
+#[derive(Component)]
+pub struct PanelRoot {
+    pub layout_rect: fn(&Layout) -> &LayoutRect,
+}
+
+#[derive(Component)]
+pub struct ConsoleRoot;
+
+// Rows of the console from the top, each panel tags the node it spawns with its row
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConsoleRow {
+    Playback,
+    Aim,
+    DriverStation,
+    Sensors,
+    Teleop,
+    Connection,
+}
+
+// Buttons that get the shared hover and press colors
+#[derive(Component)]
+pub struct PanelButton;
+
+type PanelButtonChanged = (Changed<Interaction>, With<PanelButton>);
+
+pub fn setup(mut commands: Commands) {
+    commands.spawn_bundle(NodeBundle {
+        color: UiColor(Color::NONE),
+        ..Default::default()
+    }).insert(PanelRoot {
+        layout_rect: |l| &l.console
+    }).insert(ConsoleRoot);
+}
+
+pub fn text_style(asset_server: &AssetServer, font_size: f32) -> TextStyle {
+    TextStyle {
+        font: asset_server.load("fonts/JetBrainsMono-Bold.ttf"),
+        font_size,
+        color: TEXT_COLOR,
+    }
+}
+
+// Margins scale with the font so the small rows stay compact
+pub fn text(value: &str, style: &TextStyle) -> TextBundle {
+    TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(style.font_size / 6.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(value, style.clone(), TextAlignment::default()),
+        ..Default::default()
+    }
+}
+
+pub fn row() -> NodeBundle {
+    NodeBundle {
+        color: UiColor(Color::NONE),
+        style: Style {
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+// Labels that change are reached through the button's first child
+pub fn generate_button(parent: &mut ChildBuilder, label: &str, style: &TextStyle, component: impl Component) {
+    parent.spawn_bundle(ButtonBundle {
+        color: UiColor(NORMAL_BUTTON),
+        style: Style {
+            margin: Rect::all(Val::Px(style.font_size / 6.0)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }).with_children(|button_parent| {
+        button_parent.spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Px(style.font_size / 4.0)),
+                ..Default::default()
+            },
+            text: Text::with_section(label, TextStyle { color: BUTTON_TEXT_COLOR, ..style.clone() }, TextAlignment::default()),
+            ..Default::default()
+        });
+    }).insert(component).insert(PanelButton);
+}
+
+// Only touches the text when it changed, so it isn't laid out again every frame
+pub fn set_text_if_changed(text: &mut Mut<Text>, value: String) {
+    if text.sections[0].value != value {
+        text.sections[0].value = value;
+    }
+}
+
+pub fn panel_root_updater(
+    mut query: Query<(&PanelRoot, &mut Style)>,
+    layout_changed_event: Res<Events<LayoutChangedEvent>>
+) {
+    match layout_changed_event.get_reader().iter(&layout_changed_event).next_back() {
+        None => {}
+        Some(e) => {
+            let layout: &Layout = &e.0;
+
+            for i in query.iter_mut() {
+                let (root, mut style): (&PanelRoot, Mut<Style>) = i;
+                let location = (root.layout_rect)(layout);
+                *style = Style {
+                    size: Size::new(Val::Px(location.size.x), Val::Px(location.size.y - FONT_SIZE)),
+                    justify_content: JustifyContent::FlexStart,
+                    align_items: AlignItems::FlexStart,
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::ColumnReverse,
+                    position: Rect {
+                        left: Val::Px(location.pos.x + (layout.screen_size.x / 2.0)),
+                        bottom: Val::Px(location.pos.y + (layout.screen_size.y / 2.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+            }
+        }
+    };
+}
+
+// Moves new console rows into the console, keeping them in ConsoleRow order whichever panel spawned first
+pub fn console_row_updater(
+    root_query: Query<Entity, With<ConsoleRoot>>,
+    rows: Query<(Entity, &ConsoleRow)>,
+    added: Query<&ConsoleRow, Added<ConsoleRow>>,
+    mut commands: Commands
+) {
+    let root = match root_query.get_single() {
+        Ok(r) if added.iter().next().is_some() => r,
+        _ => return
+    };
+
+    let mut sorted: Vec<(Entity, ConsoleRow)> = rows.iter().map(|(e, r)| (e, *r)).collect();
+    sorted.sort_by_key(|(_, row)| *row);
+
+    let children: Vec<Entity> = sorted.iter().map(|(e, _)| *e).collect();
+    for child in children.iter() {
+        commands.entity(*child).insert(Parent(root));
+    }
+    commands.entity(root).insert(Children::with(&children));
+}
+
+pub fn button_color_system(mut query: Query<(&Interaction, &mut UiColor), PanelButtonChanged>) {
+    for i in query.iter_mut() {
+        let (interaction, mut color): (&Interaction, Mut<UiColor>) = i;
+        *color = match *interaction {
+            Interaction::Clicked => PRESSED_BUTTON.into(),
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+    }
+}
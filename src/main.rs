@@ -9,15 +9,21 @@ extern crate uom;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use crate::auto_pathing::AutoPathingPlugin;
-use crate::auto_pathing::trajectory::Trajectory;
 
 use crate::field::FieldManagementPlugin;
 use crate::layout::{LayoutPlugin, Layout};
 use crate::robot::RobotPlugin;
-use crate::robot_connection::RobotClient;
+use crate::robot_connection::{RobotBackend, RobotConnection, RobotConnectionPlugin};
 
 fn main() {
-    let client = RobotClient::connect();
+    // Trajectories are generated natively unless a remote generator is requested
+    let backend = if std::env::args().any(|a| a == "--remote") {
+        Some(RobotBackend::Tcp)
+    } else if std::env::args().any(|a| a == "--grpc") {
+        Some(RobotBackend::Grpc)
+    } else {
+        None
+    };
 
     App::new()
         // Default Plugins
@@ -27,12 +33,13 @@ fn main() {
         // MSAA and BG color
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(ClearColor(Color::rgb(0.05, 0.05, 0.05)))
-        .insert_resource(client)
+        .insert_resource(RobotConnection::new(backend))
 
         .add_plugin(LayoutPlugin)
         .add_plugin(FieldManagementPlugin)
         .add_plugin(RobotPlugin)
         .add_plugin(AutoPathingPlugin)
+        .add_plugin(RobotConnectionPlugin)
         .add_startup_system(setup)
 
         .run();
@@ -0,0 +1,232 @@
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use uom::si::angle::radian;
+use uom::si::angular_acceleration::radian_per_second_squared;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f32::{AngularAcceleration, AngularVelocity};
+use uom::si::length::meter;
+
+use crate::field::FieldPose;
+use crate::field::objects::Hub;
+use crate::layout::ui::{self, ConsoleRow};
+use crate::robot::control::{ProfiledPIDController, ProfileState};
+use crate::robot::odometry::Odometry;
+use crate::robot::{Robot, RobotConfig, RobotState};
+
+// Port of DriveSwerveAim in swerve_sim_aim.py, the driver keeps translation while the heading tracks the HUB
+pub struct AimSettings {
+    pub enabled: bool,
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    pub max_velocity: AngularVelocity,
+    pub max_acceleration: AngularAcceleration,
+}
+
+pub struct AimController {
+    controller: ProfiledPIDController,
+    // Whether the controller ran last frame, it is reset to the current heading when aiming starts
+    active: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AimParam {
+    Kp,
+    Ki,
+    Kd,
+    MaxVelocity,
+    MaxAcceleration,
+}
+
+#[derive(Component)]
+pub enum AimButton {
+    Toggle,
+    Adjust(AimParam, f32),
+}
+
+#[derive(Component)]
+pub struct AimText(AimParam);
+
+impl Default for AimSettings {
+    fn default() -> Self {
+        // Same gains and constraints as DriveSwerveAim.initialize
+        Self {
+            enabled: false,
+            kp: 9.0,
+            ki: 0.0,
+            kd: 0.0,
+            max_velocity: AngularVelocity::new::<radian_per_second>(5.0),
+            max_acceleration: AngularAcceleration::new::<radian_per_second_squared>(20.0),
+        }
+    }
+}
+
+impl AimSettings {
+    fn get(&self, param: AimParam) -> f32 {
+        match param {
+            AimParam::Kp => self.kp,
+            AimParam::Ki => self.ki,
+            AimParam::Kd => self.kd,
+            AimParam::MaxVelocity => self.max_velocity.get::<radian_per_second>(),
+            AimParam::MaxAcceleration => self.max_acceleration.get::<radian_per_second_squared>(),
+        }
+    }
+
+    fn adjust(&mut self, param: AimParam, delta: f32) {
+        let value = (self.get(param) + delta).max(0.0);
+        match param {
+            AimParam::Kp => self.kp = value,
+            AimParam::Ki => self.ki = value,
+            AimParam::Kd => self.kd = value,
+            AimParam::MaxVelocity => self.max_velocity = AngularVelocity::new::<radian_per_second>(value),
+            AimParam::MaxAcceleration => self.max_acceleration = AngularAcceleration::new::<radian_per_second_squared>(value),
+        }
+    }
+}
+
+impl AimParam {
+    fn label(&self) -> &'static str {
+        match self {
+            AimParam::Kp => "kP",
+            AimParam::Ki => "kI",
+            AimParam::Kd => "kD",
+            AimParam::MaxVelocity => "vel",
+            AimParam::MaxAcceleration => "acc",
+        }
+    }
+
+    fn step(&self) -> f32 {
+        match self {
+            AimParam::Kp => 0.5,
+            AimParam::Ki => 0.1,
+            AimParam::Kd => 0.1,
+            AimParam::MaxVelocity => 0.5,
+            AimParam::MaxAcceleration => 2.0,
+        }
+    }
+}
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let settings = AimSettings::default();
+    commands.insert_resource(AimController {
+        controller: ProfiledPIDController::new(
+            settings.kp,
+            settings.ki,
+            settings.kd,
+            settings.max_velocity.get::<radian_per_second>(),
+            settings.max_acceleration.get::<radian_per_second_squared>()
+        ).enable_continuous_input(-PI, PI),
+        active: false,
+    });
+    commands.insert_resource(settings);
+
+    let text_style = ui::text_style(&asset_server, ui::SMALL_FONT_SIZE);
+
+    commands.spawn_bundle(ui::row()).with_children(|parent| {
+        ui::generate_button(parent, "", &text_style, AimButton::Toggle);
+
+        for param in [AimParam::Kp, AimParam::Ki, AimParam::Kd, AimParam::MaxVelocity, AimParam::MaxAcceleration] {
+            parent.spawn_bundle(ui::text(param.label(), &text_style));
+            ui::generate_button(parent, "-", &text_style, AimButton::Adjust(param, -param.step()));
+            parent.spawn_bundle(ui::text("", &text_style)).insert(AimText(param));
+            ui::generate_button(parent, "+", &text_style, AimButton::Adjust(param, param.step()));
+        }
+    }).insert(ConsoleRow::Aim);
+}
+
+pub fn aim_button_system(
+    interaction_query: Query<(&Interaction, &AimButton), Changed<Interaction>>,
+    mut settings: ResMut<AimSettings>
+) {
+    for i in interaction_query.iter() {
+        let (interaction, button): (&Interaction, &AimButton) = i;
+
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            AimButton::Toggle => settings.enabled = !settings.enabled,
+            AimButton::Adjust(param, delta) => settings.adjust(*param, *delta),
+        }
+    }
+}
+
+pub fn aim_text_updater(
+    mut text_query: Query<(&mut Text, Option<&AimText>)>,
+    button_query: Query<(&AimButton, &Children)>,
+    settings: Res<AimSettings>
+) {
+    for i in text_query.iter_mut() {
+        let (mut text, t): (Mut<Text>, Option<&AimText>) = i;
+        if let Some(AimText(param)) = t {
+            ui::set_text_if_changed(&mut text, format!("{:.1}", settings.get(*param)));
+        }
+    }
+
+    for i in button_query.iter() {
+        let (button, children): (&AimButton, &Children) = i;
+        if let (AimButton::Toggle, Ok((mut text, _))) = (button, text_query.get_mut(children[0])) {
+            ui::set_text_if_changed(&mut text, format!("Aim (H): {}", if settings.enabled { "on" } else { "off" }));
+        }
+    }
+}
+
+// H or the West button toggles aiming
+pub fn aim_toggle_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    mut settings: ResMut<AimSettings>
+) {
+    let toggle = keyboard_input.just_pressed(KeyCode::H)
+        || gamepads.iter().any(|g| buttons.just_pressed(GamepadButton(*g, GamepadButtonType::West)));
+    if toggle {
+        settings.enabled = !settings.enabled;
+    }
+}
+
+// Overrides the rotation teleop asked for, turning the robot to face the HUB as odometry sees it
+pub fn aim_updater(
+    mut robot_query: Query<&mut Robot>,
+    odometry_query: Query<&FieldPose, With<Odometry>>,
+    hub_query: Query<&FieldPose, With<Hub>>,
+    time: Res<bevy::core::Time>,
+    config: Res<RobotConfig>,
+    settings: Res<AimSettings>,
+    mut aim: ResMut<AimController>
+) {
+    let mut robot = robot_query.single_mut();
+
+    // Like the driver's own input, aiming only turns the robot during teleop
+    let (estimate, hub) = match (odometry_query.get_single(), hub_query.get_single()) {
+        (Ok(e), Ok(h)) if settings.enabled && robot.state == RobotState::Teleop => (e, h),
+        _ => {
+            aim.active = false;
+            return;
+        }
+    };
+
+    let heading = estimate.rotation.get::<radian>();
+    let target = (hub.translation.y - estimate.translation.y).get::<meter>()
+        .atan2((hub.translation.x - estimate.translation.x).get::<meter>());
+
+    let was_active = aim.active;
+    aim.active = true;
+
+    let controller = &mut aim.controller;
+    controller.pid.kp = settings.kp;
+    controller.pid.ki = settings.ki;
+    controller.pid.kd = settings.kd;
+    controller.max_velocity = settings.max_velocity.get::<radian_per_second>();
+    controller.max_acceleration = settings.max_acceleration.get::<radian_per_second_squared>();
+
+    if !was_active {
+        controller.reset(ProfileState { position: heading, velocity: robot.command.omega.get::<radian_per_second>() });
+    }
+
+    let max_omega = config.max_angular_velocity.get::<radian_per_second>();
+    let omega = controller.calculate(heading, target, time.delta_seconds()).clamp(-max_omega, max_omega);
+
+    robot.command.omega = AngularVelocity::new::<radian_per_second>(omega);
+}
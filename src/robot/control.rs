@@ -0,0 +1,128 @@
+// Plain f32 controllers in SI units, mirroring the WPILib ones the robot code uses
+
+use crate::field::wrap;
+
+pub struct PIDController {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    integral: f32,
+    prev_error: Option<f32>,
+    // Inputs wrap around between min and max, e.g. angles
+    continuous: Option<(f32, f32)>,
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct ProfileState {
+    pub position: f32,
+    pub velocity: f32,
+}
+
+// PID on a setpoint that follows a trapezoidal profile towards the goal
+pub struct ProfiledPIDController {
+    pub pid: PIDController,
+    pub max_velocity: f32,
+    pub max_acceleration: f32,
+    setpoint: ProfileState,
+}
+
+impl PIDController {
+    pub fn new(kp: f32, ki: f32, kd: f32) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            integral: 0.0,
+            prev_error: None,
+            continuous: None,
+        }
+    }
+
+    pub fn enable_continuous_input(mut self, min: f32, max: f32) -> Self {
+        self.continuous = Some((min, max));
+        self
+    }
+
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.prev_error = None;
+    }
+
+    pub fn calculate(&mut self, measurement: f32, setpoint: f32, dt: f32) -> f32 {
+        let error = self.error(measurement, setpoint);
+
+        let derivative = match self.prev_error {
+            Some(prev) if dt > 0.0 => (error - prev) / dt,
+            _ => 0.0,
+        };
+
+        self.integral += error * dt;
+        self.prev_error = Some(error);
+
+        self.kp * error + self.ki * self.integral + self.kd * derivative
+    }
+
+    fn error(&self, measurement: f32, setpoint: f32) -> f32 {
+        match self.continuous {
+            Some((min, max)) => wrap(setpoint - measurement, max - min),
+            None => setpoint - measurement,
+        }
+    }
+}
+
+impl ProfiledPIDController {
+    pub fn new(kp: f32, ki: f32, kd: f32, max_velocity: f32, max_acceleration: f32) -> Self {
+        Self {
+            pid: PIDController::new(kp, ki, kd),
+            max_velocity,
+            max_acceleration,
+            setpoint: ProfileState::default(),
+        }
+    }
+
+    pub fn enable_continuous_input(mut self, min: f32, max: f32) -> Self {
+        self.pid = self.pid.enable_continuous_input(min, max);
+        self
+    }
+
+    pub fn reset(&mut self, measurement: ProfileState) {
+        self.pid.reset();
+        self.setpoint = measurement;
+    }
+
+    pub fn calculate(&mut self, measurement: f32, goal: f32, dt: f32) -> f32 {
+        // Take the short way around, relative to where the robot actually is
+        let (goal, setpoint) = match self.pid.continuous {
+            Some((min, max)) => (
+                measurement + wrap(goal - measurement, max - min),
+                measurement + wrap(self.setpoint.position - measurement, max - min),
+            ),
+            None => (goal, self.setpoint.position),
+        };
+
+        self.setpoint = step_profile(
+            ProfileState { position: setpoint, velocity: self.setpoint.velocity },
+            goal,
+            self.max_velocity,
+            self.max_acceleration,
+            dt
+        );
+
+        self.pid.calculate(measurement, self.setpoint.position, dt)
+    }
+}
+
+// Advances a trapezoidal profile one step towards a goal at rest, braking early enough not to overshoot
+pub fn step_profile(current: ProfileState, goal: f32, max_v: f32, max_a: f32, dt: f32) -> ProfileState {
+    let distance = goal - current.position;
+
+    let desired_v = distance.signum() * max_v.min((2.0 * max_a * distance.abs()).sqrt());
+    let velocity = current.velocity + (desired_v - current.velocity).clamp(-max_a * dt, max_a * dt);
+    let step = velocity * dt;
+
+    if step.abs() >= distance.abs() && step.signum() == distance.signum() {
+        ProfileState { position: goal, velocity: 0.0 }
+    } else {
+        ProfileState { position: current.position + step, velocity }
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use uom::ConstZero;
+use uom::si::f32::Time;
+use uom::si::time::second;
+
+use crate::auto_pathing::waypoints::FieldWaypointList;
+use crate::layout::ui::{self, ConsoleRow};
+use crate::robot::playback::{Playback, PlaybackMode};
+use crate::robot::{Robot, RobotState};
+
+// Match period lengths in seconds, autonomous runs first and teleop follows straight after
+const AUTONOMOUS_DURATION: f32 = 15.0;
+const TELEOP_DURATION: f32 = 135.0;
+
+// Driver station controls, enabling the robot starts a match with the chosen auto
+#[derive(Default)]
+pub struct DriverStation {
+    pub enabled: bool,
+    // Routine run during autonomous
+    pub auto: usize,
+    // Time since the robot was enabled
+    pub match_time: Time,
+}
+
+#[derive(Component)]
+pub enum DriverStationButton {
+    Enable,
+    PreviousAuto,
+    NextAuto,
+}
+
+#[derive(Component)]
+pub enum DriverStationText {
+    Auto,
+    Timer,
+}
+
+impl DriverStation {
+    // The robot state this point of the match calls for
+    fn phase(&self) -> RobotState {
+        let t = self.match_time.get::<second>();
+        if !self.enabled || t >= AUTONOMOUS_DURATION + TELEOP_DURATION {
+            RobotState::Disabled
+        } else if t < AUTONOMOUS_DURATION {
+            RobotState::Autonomous(self.auto as u32)
+        } else {
+            RobotState::Teleop
+        }
+    }
+
+    // Time left in the current period
+    fn remaining(&self) -> Time {
+        let t = self.match_time.get::<second>();
+        let end = if t < AUTONOMOUS_DURATION { AUTONOMOUS_DURATION } else { AUTONOMOUS_DURATION + TELEOP_DURATION };
+        Time::new::<second>((end - t).max(0.0))
+    }
+}
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DriverStation::default());
+
+    let text_style = ui::text_style(&asset_server, ui::SMALL_FONT_SIZE);
+
+    commands.spawn_bundle(ui::row()).with_children(|parent| {
+        ui::generate_button(parent, "", &text_style, DriverStationButton::Enable);
+        ui::generate_button(parent, "<", &text_style, DriverStationButton::PreviousAuto);
+        parent.spawn_bundle(ui::text("", &text_style)).insert(DriverStationText::Auto);
+        ui::generate_button(parent, ">", &text_style, DriverStationButton::NextAuto);
+        parent.spawn_bundle(ui::text("", &text_style)).insert(DriverStationText::Timer);
+    }).insert(ConsoleRow::DriverStation);
+}
+
+pub fn driver_station_button_system(
+    interaction_query: Query<(&Interaction, &DriverStationButton), Changed<Interaction>>,
+    waypoints: Res<FieldWaypointList>,
+    mut ds: ResMut<DriverStation>
+) {
+    for i in interaction_query.iter() {
+        let (interaction, button): (&Interaction, &DriverStationButton) = i;
+
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let routines = waypoints.0.len();
+        match button {
+            DriverStationButton::Enable => {
+                ds.enabled = !ds.enabled;
+                ds.match_time = Time::ZERO;
+            }
+            // The auto can't change under a running match
+            _ if ds.enabled => {}
+            DriverStationButton::PreviousAuto => ds.auto = (ds.auto + routines - 1) % routines,
+            DriverStationButton::NextAuto => ds.auto = (ds.auto + 1) % routines,
+        }
+    }
+}
+
+// Runs the match clock and moves the robot between states, autonomous starts the chosen routine in follow mode
+pub fn match_updater(
+    mut robot_query: Query<&mut Robot>,
+    time: Res<bevy::core::Time>,
+    mut waypoints: ResMut<FieldWaypointList>,
+    mut playback: ResMut<Playback>,
+    mut ds: ResMut<DriverStation>
+) {
+    // Routines can be deleted while one is chosen
+    if ds.auto >= waypoints.0.len() {
+        ds.auto = 0;
+    }
+
+    if ds.enabled {
+        ds.match_time += Time::new::<second>(time.delta_seconds());
+    }
+
+    let state = ds.phase();
+    if state == RobotState::Disabled && ds.enabled {
+        // The match is over
+        ds.enabled = false;
+    }
+
+    let mut robot = robot_query.single_mut();
+    if robot.state == state {
+        return;
+    }
+
+    let previous = robot.state;
+    robot.state = state;
+
+    match state {
+        RobotState::Autonomous(routine) => playback.start(routine as usize, PlaybackMode::Follow, &mut waypoints),
+        _ => {
+            // Autonomous is over whether or not the routine finished
+            if let RobotState::Autonomous(_) = previous {
+                playback.playing = false;
+            }
+        }
+    }
+}
+
+pub fn driver_station_text_updater(
+    mut text_query: Query<(&mut Text, Option<&DriverStationText>)>,
+    button_query: Query<(&DriverStationButton, &Children)>,
+    robot_query: Query<&Robot>,
+    ds: Res<DriverStation>
+) {
+    let state = match robot_query.get_single() {
+        Ok(r) => r.state,
+        Err(_) => return
+    };
+
+    for i in text_query.iter_mut() {
+        let (mut text, t): (Mut<Text>, Option<&DriverStationText>) = i;
+        let value = match t {
+            Some(DriverStationText::Auto) => format!("Auto: {}", ds.auto),
+            Some(DriverStationText::Timer) => {
+                let remaining = ds.remaining().get::<second>();
+                match state {
+                    RobotState::Disabled => "Disabled".to_string(),
+                    RobotState::Teleop => format!("Teleop {}:{:04.1}", (remaining / 60.0) as u32, remaining % 60.0),
+                    RobotState::Autonomous(_) => format!("Autonomous {:.1} s", remaining),
+                }
+            }
+            None => continue
+        };
+        ui::set_text_if_changed(&mut text, value);
+    }
+
+    for i in button_query.iter() {
+        let (button, children): (&DriverStationButton, &Children) = i;
+        if let (DriverStationButton::Enable, Ok((mut text, _))) = (button, text_query.get_mut(children[0])) {
+            ui::set_text_if_changed(&mut text, (if ds.enabled { "Disable" } else { "Enable" }).to_string());
+        }
+    }
+}
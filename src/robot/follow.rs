@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use uom::ConstZero;
+use uom::si::angle::{degree, radian};
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f32::{Angle, AngularVelocity, Length, Time, Velocity};
+use uom::si::length::meter;
+use uom::si::velocity::meter_per_second;
+
+use crate::auto_pathing::trajectory::{TimedTrajectory, TrajectoryID, TrajectoryState};
+use crate::auto_pathing::waypoints::FieldWaypointList;
+use crate::field::{wrap_angle, FieldPose};
+use crate::robot::control::{PIDController, ProfiledPIDController, ProfileState};
+use crate::robot::kinematics::ChassisSpeeds;
+use crate::robot::odometry::Odometry;
+use crate::robot::playback::{active_trajectory, Playback, PlaybackMode};
+use crate::robot::{Robot, RobotConfig};
+
+// Port of the HolonomicDriveController FollowPath drives the robot with.
+// Feeds forward the trajectory velocity and corrects x, y and heading separately, all in field coordinates.
+pub struct HolonomicDriveController {
+    x: PIDController,
+    y: PIDController,
+    heading: ProfiledPIDController,
+}
+
+// How closely the robot (ground truth, not odometry) tracked one path
+#[derive(Default, Debug, Copy, Clone)]
+pub struct FollowReport {
+    pub max_cross_track: Length,
+    cross_track_squared: f32,
+    samples: u32,
+    pub max_heading_error: Angle,
+    // Distance and heading off the end pose once the path is done
+    pub final_error: Option<(Length, Angle)>,
+}
+
+pub struct PathFollower {
+    controller: HolonomicDriveController,
+    // Whether the controller drove the robot last frame
+    active: bool,
+    // Latest run of each path, by path id
+    pub reports: HashMap<usize, FollowReport>,
+}
+
+impl HolonomicDriveController {
+    pub fn new(config: &RobotConfig) -> Self {
+        let max_omega = config.max_angular_velocity.get::<radian_per_second>();
+        Self {
+            x: PIDController::new(1.0, 0.0, 0.0),
+            y: PIDController::new(1.0, 0.0, 0.0),
+            heading: ProfiledPIDController::new(3.0, 0.0, 0.0, max_omega, max_omega * 2.0)
+                .enable_continuous_input(-PI, PI),
+        }
+    }
+
+    // Each path has its own turn rate limit, the profile accelerates to it in half a second like the robot's default
+    pub fn set_max_angular_velocity(&mut self, max: AngularVelocity) {
+        let max_omega = max.get::<radian_per_second>();
+        self.heading.max_velocity = max_omega;
+        self.heading.max_acceleration = max_omega * 2.0;
+    }
+
+    pub fn reset(&mut self, pose: &FieldPose) {
+        self.x.reset();
+        self.y.reset();
+        self.heading.reset(ProfileState { position: pose.rotation.get::<radian>(), velocity: 0.0 });
+    }
+
+    // Robot-relative speeds that take the robot from the current pose onto the desired state
+    pub fn calculate(&mut self, current: &FieldPose, desired: &TrajectoryState, heading: Angle, dt: f32) -> ChassisSpeeds {
+        let (sin, cos) = desired.pose.rotation.get::<radian>().sin_cos();
+
+        let x_feedback = self.x.calculate(
+            current.translation.x.get::<meter>(),
+            desired.pose.translation.x.get::<meter>(),
+            dt
+        );
+        let y_feedback = self.y.calculate(
+            current.translation.y.get::<meter>(),
+            desired.pose.translation.y.get::<meter>(),
+            dt
+        );
+        let omega = self.heading.calculate(current.rotation.get::<radian>(), heading.get::<radian>(), dt);
+
+        ChassisSpeeds::from_field_relative(
+            desired.velocity * cos + Velocity::new::<meter_per_second>(x_feedback),
+            desired.velocity * sin + Velocity::new::<meter_per_second>(y_feedback),
+            AngularVelocity::new::<radian_per_second>(omega),
+            current.rotation
+        )
+    }
+}
+
+impl FollowReport {
+    pub fn rms_cross_track(&self) -> Length {
+        if self.samples == 0 {
+            return Length::ZERO;
+        }
+        Length::new::<meter>((self.cross_track_squared / self.samples as f32).sqrt())
+    }
+
+    fn record(&mut self, truth: &FieldPose, desired: &TrajectoryState, heading: Angle) {
+        // Error perpendicular to the direction of travel, the along-track part is just being early or late
+        let (sin, cos) = desired.pose.rotation.get::<radian>().sin_cos();
+        let dx = (truth.translation.x - desired.pose.translation.x).get::<meter>();
+        let dy = (truth.translation.y - desired.pose.translation.y).get::<meter>();
+        let cross_track = (-dx * sin + dy * cos).abs();
+
+        let heading_error = heading_error(truth, heading);
+
+        self.max_cross_track = self.max_cross_track.max(Length::new::<meter>(cross_track));
+        self.cross_track_squared += cross_track * cross_track;
+        self.samples += 1;
+        self.max_heading_error = self.max_heading_error.max(heading_error);
+    }
+
+    fn finish(&mut self, truth: &FieldPose, end: &FieldPose) {
+        let dx = truth.translation.x - end.translation.x;
+        let dy = truth.translation.y - end.translation.y;
+        let distance = Length::new::<meter>(dx.get::<meter>().hypot(dy.get::<meter>()));
+
+        self.final_error = Some((distance, heading_error(truth, end.rotation)));
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "track {:.3} m rms, {:.3} m max, heading {:.1}° max",
+            self.rms_cross_track().get::<meter>(),
+            self.max_cross_track.get::<meter>(),
+            self.max_heading_error.get::<degree>()
+        );
+        if let Some((distance, heading)) = self.final_error {
+            summary += &format!(", end {:.3} m {:.1}°", distance.get::<meter>(), heading.get::<degree>());
+        }
+        summary
+    }
+}
+
+fn heading_error(pose: &FieldPose, heading: Angle) -> Angle {
+    Angle::new::<radian>(wrap_angle((pose.rotation - heading).get::<radian>()).abs())
+}
+
+pub fn setup(mut commands: Commands, config: Res<RobotConfig>) {
+    commands.insert_resource(PathFollower {
+        controller: HolonomicDriveController::new(&config),
+        active: false,
+        reports: HashMap::new(),
+    });
+}
+
+// In follow mode playback time drives the setpoint, and the controller drives the simulated swerve from the odometry estimate.
+// The robot turns to the trajectory's holonomic rotation, which follows the path's rotation targets.
+pub fn follow_updater(
+    mut robot_query: Query<(&mut Robot, &mut FieldPose)>,
+    mut odometry_query: Query<(&mut Odometry, &FieldPose), Without<Robot>>,
+    trajectories: Query<(&TrajectoryID, &TimedTrajectory)>,
+    waypoints: Res<FieldWaypointList>,
+    time: Res<bevy::core::Time>,
+    playback: Res<Playback>,
+    mut follower: ResMut<PathFollower>
+) {
+    let trajectory = match active_trajectory(&trajectories, &waypoints) {
+        Some(t) if !t.0.is_empty() && playback.mode == PlaybackMode::Follow => t,
+        _ => {
+            follower.active = false;
+            return;
+        }
+    };
+
+    let (mut robot, mut truth): (Mut<Robot>, Mut<FieldPose>) = robot_query.single_mut();
+    let (mut odometry, estimate): (Mut<Odometry>, &FieldPose) = odometry_query.single_mut();
+
+    let start = trajectory.0[0].robot_pose();
+    let end = trajectory.0[trajectory.0.len() - 1].robot_pose();
+    let path = waypoints.1;
+
+    if !playback.playing {
+        if follower.active {
+            follower.active = false;
+            robot.command = ChassisSpeeds::default();
+
+            if playback.time >= trajectory.total_time() {
+                if let Some(report) = follower.reports.get_mut(&path) {
+                    report.finish(&truth, &end);
+                    info!("Path {} followed: {}", path, report.summary());
+                }
+            }
+        }
+        return;
+    }
+
+    // The estimate only catches up with a snap during the odometry update, so use the truth this frame
+    let current = if !follower.active && playback.time == Time::ZERO {
+        *truth = start;
+        odometry.reset();
+        follower.reports.insert(path, FollowReport::default());
+        start
+    } else {
+        *estimate
+    };
+
+    if !follower.active {
+        follower.active = true;
+        follower.controller.set_max_angular_velocity(waypoints.2[path].config.max_angular_velocity);
+        follower.controller.reset(&current);
+    }
+
+    let desired = match trajectory.sample(playback.time) {
+        Some(s) => s,
+        None => return
+    };
+
+    robot.command = follower.controller.calculate(&current, &desired, desired.holonomic_rotation, time.delta_seconds());
+
+    if let Some(report) = follower.reports.get_mut(&path) {
+        report.record(&truth, &desired, desired.holonomic_rotation);
+    }
+}
@@ -0,0 +1,221 @@
+use std::f32::consts::PI;
+use uom::ConstZero;
+use uom::si::angle::radian;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f32::{Angle, AngularVelocity, Velocity};
+use uom::si::length::meter;
+use uom::si::velocity::meter_per_second;
+
+use crate::field::{wrap_angle, FieldPosition};
+
+// Robot-relative velocities, x forward and y to the left
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct ChassisSpeeds {
+    pub vx: Velocity,
+    pub vy: Velocity,
+    pub omega: AngularVelocity,
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct SwerveModuleState {
+    pub speed: Velocity,
+    pub angle: Angle,
+}
+
+// Module positions are relative to the robot center, in the same order as the module states
+pub struct SwerveDriveKinematics {
+    modules: [FieldPosition; 4],
+}
+
+impl ChassisSpeeds {
+    pub fn new(vx: Velocity, vy: Velocity, omega: AngularVelocity) -> Self {
+        Self { vx, vy, omega }
+    }
+
+    // Converts field-relative velocities to robot-relative ones for a robot facing heading
+    pub fn from_field_relative(vx: Velocity, vy: Velocity, omega: AngularVelocity, heading: Angle) -> Self {
+        let (sin, cos) = heading.get::<radian>().sin_cos();
+        Self {
+            vx: vx * cos + vy * sin,
+            vy: -vx * sin + vy * cos,
+            omega,
+        }
+    }
+}
+
+impl SwerveModuleState {
+    pub fn new(speed: Velocity, angle: Angle) -> Self {
+        Self { speed, angle }
+    }
+
+    // Flips the wheel direction instead of turning the module more than 90 degrees
+    pub fn optimize(&self, current_angle: Angle) -> Self {
+        let delta = wrap_angle(self.angle.get::<radian>() - current_angle.get::<radian>());
+
+        if delta.abs() > PI / 2.0 {
+            Self {
+                speed: -self.speed,
+                angle: Angle::new::<radian>(wrap_angle(self.angle.get::<radian>() + PI)),
+            }
+        } else {
+            *self
+        }
+    }
+}
+
+impl SwerveDriveKinematics {
+    pub fn new(modules: [FieldPosition; 4]) -> Self {
+        Self { modules }
+    }
+
+    pub fn to_module_states(&self, speeds: &ChassisSpeeds) -> [SwerveModuleState; 4] {
+        let vx = speeds.vx.get::<meter_per_second>();
+        let vy = speeds.vy.get::<meter_per_second>();
+        let omega = speeds.omega.get::<radian_per_second>();
+
+        self.modules.map(|m| {
+            let mvx = vx - omega * m.y.get::<meter>();
+            let mvy = vy + omega * m.x.get::<meter>();
+
+            SwerveModuleState::new(
+                Velocity::new::<meter_per_second>(mvx.hypot(mvy)),
+                Angle::new::<radian>(mvy.atan2(mvx))
+            )
+        })
+    }
+
+    // Least squares fit of the chassis speeds that best explain the module states
+    pub fn to_chassis_speeds(&self, states: &[SwerveModuleState; 4]) -> ChassisSpeeds {
+        // Each module contributes the rows [1, 0, -y] and [0, 1, x], solved through the normal equations
+        let mut ata = [[0.0_f32; 3]; 3];
+        let mut atb = [0.0_f32; 3];
+
+        for (m, s) in self.modules.iter().zip(states.iter()) {
+            let (x, y) = (m.x.get::<meter>(), m.y.get::<meter>());
+            let (sin, cos) = s.angle.get::<radian>().sin_cos();
+            let (mvx, mvy) = (s.speed.get::<meter_per_second>() * cos, s.speed.get::<meter_per_second>() * sin);
+
+            let rows = [[1.0, 0.0, -y], [0.0, 1.0, x]];
+            for (row, b) in rows.iter().zip([mvx, mvy]) {
+                for i in 0..3 {
+                    for j in 0..3 {
+                        ata[i][j] += row[i] * row[j];
+                    }
+                    atb[i] += row[i] * b;
+                }
+            }
+        }
+
+        match solve_3x3(ata, atb) {
+            Some([vx, vy, omega]) => ChassisSpeeds::new(
+                Velocity::new::<meter_per_second>(vx),
+                Velocity::new::<meter_per_second>(vy),
+                AngularVelocity::new::<radian_per_second>(omega)
+            ),
+            None => ChassisSpeeds::default()
+        }
+    }
+
+    // Scales every module down together so none exceeds max_speed, keeping the direction of travel
+    pub fn desaturate_wheel_speeds(states: &mut [SwerveModuleState; 4], max_speed: Velocity) {
+        let fastest = states.iter().map(|s| s.speed.abs()).fold(Velocity::ZERO, |a, b| if b > a { b } else { a });
+
+        if fastest <= max_speed || fastest <= Velocity::ZERO {
+            return;
+        }
+
+        let scale: f32 = (max_speed / fastest).value;
+        for s in states.iter_mut() {
+            s.speed *= scale;
+        }
+    }
+}
+
+// Cramer's rule, None if the system is singular
+fn solve_3x3(m: [[f32; 3]; 3], b: [f32; 3]) -> Option<[f32; 3]> {
+    let det = |m: &[[f32; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let d = det(&m);
+    if d.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let mut result = [0.0; 3];
+    for (col, r) in result.iter_mut().enumerate() {
+        let mut mc = m;
+        for row in 0..3 {
+            mc[row][col] = b[row];
+        }
+        *r = det(&mc) / d;
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uom::si::angle::degree;
+    use uom::si::f32::Length;
+
+    fn kinematics() -> SwerveDriveKinematics {
+        let module = |x: f32, y: f32| FieldPosition::new(Length::new::<meter>(x), Length::new::<meter>(y));
+        SwerveDriveKinematics::new([module(0.3, 0.3), module(0.3, -0.3), module(-0.3, 0.3), module(-0.3, -0.3)])
+    }
+
+    fn state(speed: f32, degrees: f32) -> SwerveModuleState {
+        SwerveModuleState::new(Velocity::new::<meter_per_second>(speed), Angle::new::<degree>(degrees))
+    }
+
+    #[test]
+    fn module_states_round_trip() {
+        let kinematics = kinematics();
+        let speeds = ChassisSpeeds::new(
+            Velocity::new::<meter_per_second>(1.5),
+            Velocity::new::<meter_per_second>(-0.5),
+            AngularVelocity::new::<radian_per_second>(2.0)
+        );
+
+        let result = kinematics.to_chassis_speeds(&kinematics.to_module_states(&speeds));
+
+        assert!((result.vx - speeds.vx).abs().get::<meter_per_second>() < 1e-4);
+        assert!((result.vy - speeds.vy).abs().get::<meter_per_second>() < 1e-4);
+        assert!((result.omega - speeds.omega).abs().get::<radian_per_second>() < 1e-4);
+    }
+
+    #[test]
+    fn desaturate_keeps_ratios() {
+        let mut states = [state(1.0, 10.0), state(-2.0, 20.0), state(3.0, 30.0), state(4.0, 40.0)];
+        let before = states;
+
+        SwerveDriveKinematics::desaturate_wheel_speeds(&mut states, Velocity::new::<meter_per_second>(2.0));
+
+        for (s, b) in states.iter().zip(before.iter()) {
+            assert!((s.speed.get::<meter_per_second>() - b.speed.get::<meter_per_second>() / 2.0).abs() < 1e-6);
+            assert_eq!(s.angle, b.angle);
+        }
+
+        SwerveDriveKinematics::desaturate_wheel_speeds(&mut states, Velocity::new::<meter_per_second>(5.0));
+        assert_eq!(states[3].speed, Velocity::new::<meter_per_second>(2.0));
+    }
+
+    #[test]
+    fn optimize_flips_above_90_degrees() {
+        let current = Angle::new::<degree>(0.0);
+
+        assert_eq!(state(1.0, 80.0).optimize(current), state(1.0, 80.0));
+        assert_eq!(state(1.0, -89.0).optimize(current), state(1.0, -89.0));
+
+        let flipped = state(1.0, 100.0).optimize(current);
+        assert_eq!(flipped.speed, Velocity::new::<meter_per_second>(-1.0));
+        assert!((flipped.angle.get::<degree>() + 80.0).abs() < 1e-3);
+
+        let flipped = state(1.0, -170.0).optimize(Angle::new::<degree>(20.0));
+        assert_eq!(flipped.speed, Velocity::new::<meter_per_second>(-1.0));
+        assert!((flipped.angle.get::<degree>() - 10.0).abs() < 1e-3);
+    }
+}
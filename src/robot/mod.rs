@@ -0,0 +1,197 @@
+pub mod aim;
+pub mod control;
+pub mod driver_station;
+pub mod follow;
+pub mod kinematics;
+pub mod odometry;
+pub mod playback;
+pub mod swerve;
+pub mod teleop;
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use uom::si::angle::radian;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f32::{Angle, AngularVelocity, Length, Time, Velocity};
+use uom::si::time::second;
+use uom::si::velocity::meter_per_second;
+use uom::ConstZero;
+
+use crate::field::render::FieldZ;
+use crate::field::shapes::FieldRectangle;
+use crate::field::{FieldPose, FieldPosition};
+use crate::robot::kinematics::{ChassisSpeeds, SwerveDriveKinematics, SwerveModuleState};
+use crate::robot::odometry::{Gyro, SensorConfig};
+use crate::robot::playback::Playback;
+use crate::robot::swerve::{SwerveModule, SwerveModuleConfig};
+use uom::si::length::{inch, meter};
+
+pub struct RobotPlugin;
+
+#[derive(Component)]
+pub struct Robot {
+    pub state: RobotState,
+    // Robot-relative speeds the drivetrain is asked for
+    pub command: ChassisSpeeds,
+    // Front left, front right, back left, back right
+    pub modules: [SwerveModule; 4],
+}
+
+pub struct RobotConfig {
+    pub frame_size: Length,
+    pub track_width: Length,
+    pub wheel_base: Length,
+    pub max_speed: Velocity,
+    pub max_angular_velocity: AngularVelocity,
+    pub module: SwerveModuleConfig,
+    pub sensors: SensorConfig,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RobotState {
+    Disabled,
+    Teleop,
+    Autonomous(u32), // Routine number?
+}
+
+impl Plugin for RobotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RobotConfig::default());
+        app.add_startup_system(setup);
+        app.add_startup_system(playback::setup);
+        app.add_startup_system(odometry::setup);
+        app.add_startup_system(teleop::setup);
+        app.add_startup_system(aim::setup);
+        app.add_startup_system(follow::setup);
+        app.add_startup_system(driver_station::setup);
+        app.add_system(driver_station::match_updater.before("teleop"));
+        app.add_system(driver_station::driver_station_button_system);
+        app.add_system(driver_station::driver_station_text_updater);
+        app.add_system(teleop::teleop_updater.label("teleop"));
+        // Aiming replaces the rotation the driver asked for
+        app.add_system(aim::aim_updater.label("aim").after("teleop"));
+        // Following a path overrides the driver entirely, it sees playback time before it advances so a run starts from zero
+        app.add_system(follow::follow_updater.after("aim").before("playback").before("swerve_drive"));
+        app.add_system(aim::aim_toggle_system);
+        app.add_system(aim::aim_button_system);
+        app.add_system(aim::aim_text_updater);
+        app.add_system(teleop::teleop_status_updater);
+        app.add_system(swerve_drive_updater.label("swerve_drive"));
+        // Sensors read the pose the drivetrain just moved to
+        app.add_system(odometry::odometry_updater.after("swerve_drive"));
+        app.add_system(odometry::sensor_button_system);
+        app.add_system(odometry::sensor_text_updater);
+        app.add_system(playback::playback_button_system);
+        app.add_system(playback::playback_slider_system);
+        app.add_system(playback::playback_updater.label("playback"));
+        app.add_system(playback::playback_ui_updater);
+    }
+}
+
+impl RobotConfig {
+    // Module positions relative to the robot center, x forward and y to the left
+    pub fn module_positions(&self) -> [FieldPosition; 4] {
+        let x = self.wheel_base / 2.0;
+        let y = self.track_width / 2.0;
+        [
+            FieldPosition::new(x, y),
+            FieldPosition::new(x, -y),
+            FieldPosition::new(-x, y),
+            FieldPosition::new(-x, -y),
+        ]
+    }
+
+    pub fn kinematics(&self) -> SwerveDriveKinematics {
+        SwerveDriveKinematics::new(self.module_positions())
+    }
+}
+
+impl Default for RobotConfig {
+    fn default() -> Self {
+        Self {
+            frame_size: Length::new::<inch>(29.0),
+            track_width: Length::new::<inch>(23.0),
+            wheel_base: Length::new::<inch>(23.0),
+            max_speed: Velocity::new::<meter_per_second>(5.0),
+            max_angular_velocity: AngularVelocity::new::<radian_per_second>(3.0),
+            module: SwerveModuleConfig::default(),
+            sensors: SensorConfig::default(),
+        }
+    }
+}
+
+fn setup(mut commands: Commands, config: Res<RobotConfig>) {
+    let robot_shape = shapes::Rectangle::default();
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &robot_shape,
+            DrawMode::Fill(FillMode::color(Color::GRAY)),
+            Transform::default(),
+        ))
+        .insert(FieldPose {
+            translation: FieldPosition::new(Length::new::<meter>(10.0), Length::new::<meter>(5.0)),
+            rotation: Angle::ZERO,
+        })
+        .insert(FieldRectangle {
+            width: config.frame_size,
+            height: config.frame_size,
+            origin: RectangleOrigin::Center,
+        })
+        .insert(Robot {
+            state: RobotState::Disabled,
+            command: ChassisSpeeds::default(),
+            modules: Default::default(),
+        })
+        .insert(Gyro::default())
+        .insert(FieldZ::ROBOT);
+}
+
+// Steps the simulated modules towards the commanded chassis speeds and moves the robot with them
+fn swerve_drive_updater(
+    mut query: Query<(&mut Robot, &mut FieldPose)>,
+    time: Res<bevy::core::Time>,
+    playback: Res<Playback>,
+    config: Res<RobotConfig>
+) {
+    if playback.moves_robot() {
+        return;
+    }
+
+    let (mut robot, mut pose): (Mut<Robot>, Mut<FieldPose>) = query.single_mut();
+
+    let kinematics = config.kinematics();
+    let mut targets = kinematics.to_module_states(&robot.command);
+    SwerveDriveKinematics::desaturate_wheel_speeds(&mut targets, config.module.drive_max_velocity);
+
+    let dt = Time::new::<second>(time.delta_seconds());
+    let mut states = [SwerveModuleState::default(); 4];
+
+    for ((module, target), state) in robot.modules.iter_mut().zip(targets.iter()).zip(states.iter_mut()) {
+        // A stopped module keeps pointing where it was
+        let target = if target.speed == Velocity::ZERO {
+            SwerveModuleState::new(Velocity::ZERO, module.angle)
+        } else {
+            target.optimize(module.angle)
+        };
+
+        if module.target != target || module.is_moving() {
+            module.target = target;
+            module.update(&config.module, dt);
+        }
+
+        *state = module.state();
+    }
+
+    let actual = kinematics.to_chassis_speeds(&states);
+    if actual == ChassisSpeeds::default() {
+        return;
+    }
+
+    let turn = Angle::new::<radian>(actual.omega.get::<radian_per_second>() * dt.get::<second>());
+    // Translate along the heading halfway through the turn
+    let (sin, cos) = (pose.rotation + turn / 2.0).get::<radian>().sin_cos();
+
+    pose.translation.x += (actual.vx * cos - actual.vy * sin) * dt;
+    pose.translation.y += (actual.vx * sin + actual.vy * cos) * dt;
+    pose.rotation += turn;
+}
@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rand::Rng;
+use uom::si::angle::{degree, radian};
+use uom::si::angular_velocity::{degree_per_second, radian_per_second};
+use uom::si::f32::{Angle, AngularVelocity, Time};
+use uom::si::time::second;
+
+use crate::field::{wrap_angle, FieldPose};
+use crate::field::render::FieldZ;
+use crate::field::shapes::FieldRectangle;
+use crate::layout::ui::{self, ConsoleRow};
+use crate::robot::kinematics::SwerveModuleState;
+use crate::robot::playback::Playback;
+use crate::robot::{Robot, RobotConfig};
+
+// Standard deviations of the simulated sensor errors, all zero gives perfect odometry
+#[derive(Copy, Clone)]
+pub struct SensorConfig {
+    // Fraction of each wheel distance increment
+    pub encoder_noise: f32,
+    // Added to every gyro reading, does not accumulate
+    pub gyro_noise: Angle,
+    // Constant error in the measured turn rate, the heading error grows linearly with it
+    pub gyro_bias: AngularVelocity,
+    // How far the turn rate error random walks in one second
+    pub gyro_drift: AngularVelocity,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SensorParam {
+    EncoderNoise,
+    GyroNoise,
+    GyroBias,
+    GyroDrift,
+}
+
+#[derive(Component)]
+pub struct SensorButton(SensorParam, f32);
+
+#[derive(Component)]
+pub struct SensorText(SensorParam);
+
+// Simulated gyro on the robot, reading the ground truth heading with accumulated errors
+#[derive(Component, Default)]
+pub struct Gyro {
+    reading: Angle,
+    error: Angle,
+    drift: AngularVelocity,
+    offset: Angle,
+}
+
+// Pose estimate built from the wheel encoders and the gyro, drawn as a ghost robot
+#[derive(Component, Default)]
+pub struct Odometry {
+    // Estimated heading minus gyro heading, set when the estimate is reset
+    heading_offset: Angle,
+    initialized: bool,
+}
+
+const GHOST_COLOR: Color = Color::rgba(0.3, 0.8, 1.0, 0.8);
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            encoder_noise: 0.02,
+            gyro_noise: Angle::new::<degree>(0.1),
+            gyro_bias: AngularVelocity::new::<degree_per_second>(0.05),
+            gyro_drift: AngularVelocity::new::<degree_per_second>(0.02),
+        }
+    }
+}
+
+impl SensorConfig {
+    // In the units the console shows, percent and degrees
+    fn get(&self, param: SensorParam) -> f32 {
+        match param {
+            SensorParam::EncoderNoise => self.encoder_noise * 100.0,
+            SensorParam::GyroNoise => self.gyro_noise.get::<degree>(),
+            SensorParam::GyroBias => self.gyro_bias.get::<degree_per_second>(),
+            SensorParam::GyroDrift => self.gyro_drift.get::<degree_per_second>(),
+        }
+    }
+
+    fn adjust(&mut self, param: SensorParam, delta: f32) {
+        let value = (self.get(param) + delta).max(0.0);
+        match param {
+            SensorParam::EncoderNoise => self.encoder_noise = value / 100.0,
+            SensorParam::GyroNoise => self.gyro_noise = Angle::new::<degree>(value),
+            SensorParam::GyroBias => self.gyro_bias = AngularVelocity::new::<degree_per_second>(value),
+            SensorParam::GyroDrift => self.gyro_drift = AngularVelocity::new::<degree_per_second>(value),
+        }
+    }
+}
+
+impl SensorParam {
+    fn label(&self) -> &'static str {
+        match self {
+            SensorParam::EncoderNoise => "enc %",
+            SensorParam::GyroNoise => "gyro °",
+            SensorParam::GyroBias => "bias °/s",
+            SensorParam::GyroDrift => "drift °/s",
+        }
+    }
+
+    fn step(&self) -> f32 {
+        match self {
+            SensorParam::EncoderNoise => 0.5,
+            SensorParam::GyroNoise => 0.05,
+            SensorParam::GyroBias => 0.05,
+            SensorParam::GyroDrift => 0.01,
+        }
+    }
+}
+
+impl Gyro {
+    // Heading as the robot code would see it, relative to the last reset
+    pub fn heading(&self) -> Angle {
+        Angle::new::<radian>(wrap_angle((self.reading - self.offset).get::<radian>()))
+    }
+
+    pub fn reset(&mut self) {
+        self.offset = self.reading;
+    }
+
+    fn update(&mut self, true_heading: Angle, config: &SensorConfig, dt: Time, rng: &mut impl Rng) {
+        let dt = dt.get::<second>();
+
+        self.drift += config.gyro_drift * (gaussian(rng) * dt.sqrt());
+        self.error += Angle::new::<radian>((config.gyro_bias + self.drift).get::<radian_per_second>() * dt);
+        self.reading = true_heading + self.error + config.gyro_noise * gaussian(rng);
+    }
+}
+
+impl Odometry {
+    // Snaps the estimate back to the ground truth on the next update
+    pub fn reset(&mut self) {
+        self.initialized = false;
+    }
+}
+
+// Standard normal sample, Box-Muller
+fn gaussian(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
+pub fn setup(mut commands: Commands, config: Res<RobotConfig>, asset_server: Res<AssetServer>) {
+    let text_style = ui::text_style(&asset_server, ui::SMALL_FONT_SIZE);
+
+    // The same noise, bias and drift RobotConfig starts with, tunable while driving
+    commands.spawn_bundle(ui::row()).with_children(|parent| {
+        parent.spawn_bundle(ui::text("Sensors", &text_style));
+
+        for param in [SensorParam::EncoderNoise, SensorParam::GyroNoise, SensorParam::GyroBias, SensorParam::GyroDrift] {
+            parent.spawn_bundle(ui::text(param.label(), &text_style));
+            ui::generate_button(parent, "-", &text_style, SensorButton(param, -param.step()));
+            parent.spawn_bundle(ui::text("", &text_style)).insert(SensorText(param));
+            ui::generate_button(parent, "+", &text_style, SensorButton(param, param.step()));
+        }
+    }).insert(ConsoleRow::Sensors);
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle::default(),
+            DrawMode::Stroke(StrokeMode::new(GHOST_COLOR, 2.0)),
+            Transform::default(),
+        ))
+        .insert(FieldPose::default())
+        .insert(FieldRectangle {
+            width: config.frame_size,
+            height: config.frame_size,
+            origin: RectangleOrigin::Center,
+        })
+        .insert(Odometry::default())
+        .insert(FieldZ::ODOMETRY);
+}
+
+pub fn odometry_updater(
+    mut robot_query: Query<(&Robot, &FieldPose, &mut Gyro)>,
+    mut odometry_query: Query<(&mut Odometry, &mut FieldPose), Without<Robot>>,
+    time: Res<bevy::core::Time>,
+    playback: Res<Playback>,
+    config: Res<RobotConfig>
+) {
+    let (robot, truth, mut gyro): (&Robot, &FieldPose, Mut<Gyro>) = robot_query.single_mut();
+    let mut rng = rand::thread_rng();
+    let dt = Time::new::<second>(time.delta_seconds());
+
+    gyro.update(truth.rotation, &config.sensors, dt, &mut rng);
+
+    for i in odometry_query.iter_mut() {
+        let (mut odometry, mut estimate): (Mut<Odometry>, Mut<FieldPose>) = i;
+
+        // Playback moves the robot without driving the modules, so there is nothing to measure
+        if !odometry.initialized || playback.moves_robot() {
+            *estimate = *truth;
+            odometry.heading_offset = truth.rotation - gyro.reading;
+            odometry.initialized = true;
+            continue;
+        }
+
+        // Wheel distances travelled this tick, as states over one second so forward kinematics gives distances
+        let deltas = robot.modules.map(|m| SwerveModuleState::new(
+            m.speed * dt.get::<second>() * (1.0 + config.sensors.encoder_noise * gaussian(&mut rng)),
+            m.angle
+        ));
+        let twist = config.kinematics().to_chassis_speeds(&deltas);
+
+        let previous = estimate.rotation.get::<radian>();
+        let current = (gyro.reading + odometry.heading_offset).get::<radian>();
+        let mid = previous + wrap_angle(current - previous) / 2.0;
+        let (sin, cos) = mid.sin_cos();
+
+        let unit = Time::new::<second>(1.0);
+        estimate.translation.x += (twist.vx * cos - twist.vy * sin) * unit;
+        estimate.translation.y += (twist.vx * sin + twist.vy * cos) * unit;
+        estimate.rotation = Angle::new::<radian>(current);
+    }
+}
+
+pub fn sensor_button_system(
+    interaction_query: Query<(&Interaction, &SensorButton), Changed<Interaction>>,
+    mut config: ResMut<RobotConfig>
+) {
+    for i in interaction_query.iter() {
+        let (interaction, button): (&Interaction, &SensorButton) = i;
+
+        if *interaction == Interaction::Clicked {
+            config.sensors.adjust(button.0, button.1);
+        }
+    }
+}
+
+pub fn sensor_text_updater(mut query: Query<(&mut Text, &SensorText)>, config: Res<RobotConfig>) {
+    for i in query.iter_mut() {
+        let (mut text, t): (Mut<Text>, &SensorText) = i;
+        ui::set_text_if_changed(&mut text, format!("{:.2}", config.sensors.get(t.0)));
+    }
+}
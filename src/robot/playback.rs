@@ -0,0 +1,275 @@
+use bevy::prelude::*;
+use uom::ConstZero;
+use uom::si::f32::Time;
+use uom::si::time::second;
+
+use crate::auto_pathing::trajectory::{TimedTrajectory, TrajectoryID};
+use crate::auto_pathing::waypoints::FieldWaypointList;
+use crate::field::FieldPose;
+use crate::layout::render::FONT_SIZE;
+use crate::layout::ui::{self, ConsoleRow};
+use crate::robot::follow::PathFollower;
+use crate::robot::Robot;
+
+// Position of the robot along the active routine's trajectory
+#[derive(Default)]
+pub struct Playback {
+    pub playing: bool,
+    pub time: Time,
+    pub mode: PlaybackMode,
+    // Routine the playback time belongs to, playback stops when another one is selected
+    routine: Option<usize>,
+}
+
+// Ideal playback puts the robot exactly on the trajectory, follow drives it there with the path controller
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub enum PlaybackMode {
+    #[default]
+    Ideal,
+    Follow,
+}
+
+#[derive(Component)]
+pub enum PlaybackButton {
+    PlayPause,
+    Restart,
+    Mode,
+}
+
+#[derive(Component)]
+pub struct PlaybackSlider;
+
+#[derive(Component)]
+pub struct PlaybackSliderFill;
+
+#[derive(Component)]
+pub struct PlaybackTimeText;
+
+const SLIDER_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const SLIDER_FILL_COLOR: Color = Color::rgb(0.55, 0.75, 0.55);
+
+const SLIDER_WIDTH: f32 = 400.0;
+
+impl Playback {
+    // Whether playback is placing the robot itself, rather than the drivetrain moving it
+    pub fn moves_robot(&self) -> bool {
+        self.playing && self.mode == PlaybackMode::Ideal
+    }
+
+    // Plays a routine from the beginning, selecting it if it isn't already
+    pub fn start(&mut self, routine: usize, mode: PlaybackMode, waypoints: &mut FieldWaypointList) {
+        waypoints.1 = routine;
+        self.routine = Some(routine);
+        self.mode = mode;
+        self.time = Time::ZERO;
+        self.playing = true;
+    }
+}
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Playback::default());
+
+    let text_style = ui::text_style(&asset_server, FONT_SIZE);
+
+    commands.spawn_bundle(ui::row()).with_children(|parent| {
+        ui::generate_button(parent, "Play", &text_style, PlaybackButton::PlayPause);
+        ui::generate_button(parent, "Restart", &text_style, PlaybackButton::Restart);
+        ui::generate_button(parent, "Ideal", &text_style, PlaybackButton::Mode);
+
+        parent.spawn_bundle(ButtonBundle {
+            color: UiColor(SLIDER_COLOR),
+            style: Style {
+                size: Size::new(Val::Px(SLIDER_WIDTH), Val::Px(FONT_SIZE * 0.6)),
+                margin: Rect::all(Val::Px(5.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        }).with_children(|slider| {
+            slider.spawn_bundle(NodeBundle {
+                color: UiColor(SLIDER_FILL_COLOR),
+                style: Style {
+                    size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }).insert(PlaybackSliderFill);
+        }).insert(PlaybackSlider);
+
+        parent.spawn_bundle(ui::text("", &text_style)).insert(PlaybackTimeText);
+    }).insert(ConsoleRow::Playback);
+}
+
+pub fn active_trajectory<'a>(
+    query: &'a Query<(&TrajectoryID, &TimedTrajectory)>,
+    waypoints: &FieldWaypointList
+) -> Option<&'a TimedTrajectory> {
+    query.iter().find(|(id, _)| id.0 == waypoints.1).map(|(_, t)| t)
+}
+
+pub fn playback_button_system(
+    interaction_query: Query<(&Interaction, &PlaybackButton), Changed<Interaction>>,
+    trajectories: Query<(&TrajectoryID, &TimedTrajectory)>,
+    waypoints: Res<FieldWaypointList>,
+    mut playback: ResMut<Playback>
+) {
+    for i in interaction_query.iter() {
+        let (interaction, button): (&Interaction, &PlaybackButton) = i;
+
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let total = active_trajectory(&trajectories, &waypoints).map_or(Time::ZERO, |t| t.total_time());
+
+        match button {
+            PlaybackButton::PlayPause => {
+                // Playing from the end starts over
+                if !playback.playing && playback.time >= total {
+                    playback.time = Time::ZERO;
+                }
+                playback.playing = !playback.playing;
+            }
+            PlaybackButton::Restart => {
+                playback.time = Time::ZERO;
+                playback.playing = true;
+            }
+            PlaybackButton::Mode => {
+                playback.playing = false;
+                playback.time = Time::ZERO;
+                playback.mode = match playback.mode {
+                    PlaybackMode::Ideal => PlaybackMode::Follow,
+                    PlaybackMode::Follow => PlaybackMode::Ideal,
+                };
+            }
+        }
+    }
+}
+
+// Dragging along the slider pauses playback and seeks to the cursor
+pub fn playback_slider_system(
+    query: Query<(&Interaction, &Node, &GlobalTransform), With<PlaybackSlider>>,
+    trajectories: Query<(&TrajectoryID, &TimedTrajectory)>,
+    waypoints: Res<FieldWaypointList>,
+    windows: Res<Windows>,
+    mut playback: ResMut<Playback>
+) {
+    let cursor = match windows.get_primary().and_then(|w| w.cursor_position()) {
+        Some(c) => c,
+        None => return
+    };
+
+    let total = match active_trajectory(&trajectories, &waypoints) {
+        Some(t) => t.total_time(),
+        None => return
+    };
+
+    for i in query.iter() {
+        let (interaction, node, transform): (&Interaction, &Node, &GlobalTransform) = i;
+
+        if *interaction != Interaction::Clicked || node.size.x <= 0.0 {
+            continue;
+        }
+
+        let left = transform.translation.x - node.size.x / 2.0;
+        let fraction = ((cursor.x - left) / node.size.x).clamp(0.0, 1.0);
+
+        playback.playing = false;
+        playback.time = total * fraction;
+    }
+}
+
+// Advances playback and moves the robot to the matching point of the active trajectory
+pub fn playback_updater(
+    mut robot_query: Query<&mut FieldPose, With<Robot>>,
+    trajectories: Query<(&TrajectoryID, &TimedTrajectory)>,
+    waypoints: Res<FieldWaypointList>,
+    time: Res<bevy::core::Time>,
+    mut playback: ResMut<Playback>
+) {
+    if playback.routine != Some(waypoints.1) {
+        playback.routine = Some(waypoints.1);
+        playback.playing = false;
+        playback.time = Time::ZERO;
+    }
+
+    let trajectory = match active_trajectory(&trajectories, &waypoints) {
+        Some(t) if !t.0.is_empty() => t,
+        _ => {
+            if playback.playing {
+                playback.playing = false;
+            }
+            return;
+        }
+    };
+
+    let total = trajectory.total_time();
+
+    if playback.playing {
+        playback.time += Time::new::<second>(time.delta_seconds());
+        if playback.time >= total {
+            playback.time = total;
+            playback.playing = false;
+        }
+    } else if playback.time > total {
+        // The trajectory was regenerated shorter than the current playback time
+        playback.time = total;
+    }
+
+    if !playback.is_changed() || playback.mode == PlaybackMode::Follow {
+        return;
+    }
+
+    if let Some(state) = trajectory.sample(playback.time) {
+        for mut pose in robot_query.iter_mut() {
+            *pose = state.robot_pose();
+        }
+    }
+}
+
+pub fn playback_ui_updater(
+    mut text_query: Query<(&mut Text, Option<&PlaybackTimeText>)>,
+    button_query: Query<(&PlaybackButton, &Children)>,
+    mut fill_query: Query<&mut Style, With<PlaybackSliderFill>>,
+    trajectories: Query<(&TrajectoryID, &TimedTrajectory)>,
+    waypoints: Res<FieldWaypointList>,
+    follower: Res<PathFollower>,
+    playback: Res<Playback>
+) {
+    let total = active_trajectory(&trajectories, &waypoints).map_or(Time::ZERO, |t| t.total_time());
+
+    let mut time_value = format!("{:.2} / {:.2} s", playback.time.get::<second>(), total.get::<second>());
+    if playback.mode == PlaybackMode::Follow {
+        if let Some(report) = follower.reports.get(&waypoints.1) {
+            time_value += &format!(" ({})", report.summary());
+        }
+    }
+    let fraction = if total > Time::ZERO { (playback.time / total).value } else { 0.0 };
+
+    for i in text_query.iter_mut() {
+        let (mut text, time_text): (Mut<Text>, Option<&PlaybackTimeText>) = i;
+        if time_text.is_some() {
+            ui::set_text_if_changed(&mut text, time_value.clone());
+        }
+    }
+
+    for i in button_query.iter() {
+        let (button, children): (&PlaybackButton, &Children) = i;
+        let label = match button {
+            PlaybackButton::PlayPause => if playback.playing { "Pause" } else { "Play" },
+            PlaybackButton::Restart => continue,
+            PlaybackButton::Mode => match playback.mode {
+                PlaybackMode::Ideal => "Ideal",
+                PlaybackMode::Follow => "Follow",
+            },
+        };
+        if let Ok((mut text, _)) = text_query.get_mut(children[0]) {
+            ui::set_text_if_changed(&mut text, label.to_string());
+        }
+    }
+
+    for mut style in fill_query.iter_mut() {
+        if style.size.width != Val::Percent(fraction * 100.0) {
+            style.size.width = Val::Percent(fraction * 100.0);
+        }
+    }
+}
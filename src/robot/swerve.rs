@@ -0,0 +1,87 @@
+use uom::ConstZero;
+use uom::si::acceleration::meter_per_second_squared;
+use uom::si::angle::radian;
+use uom::si::angular_acceleration::radian_per_second_squared;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::f32::{Acceleration, Angle, AngularAcceleration, AngularVelocity, Time, Velocity};
+use uom::si::time::second;
+use uom::si::velocity::meter_per_second;
+
+use crate::field::wrap_angle;
+use crate::robot::control::{step_profile, ProfileState};
+use crate::robot::kinematics::SwerveModuleState;
+
+// Motor limits for every module, defaults match TestSwerveNode in swerve_sim_subsystem.py
+#[derive(Copy, Clone)]
+pub struct SwerveModuleConfig {
+    pub steer_max_velocity: AngularVelocity,
+    pub steer_max_acceleration: AngularAcceleration,
+    pub drive_max_velocity: Velocity,
+    pub drive_max_acceleration: Acceleration,
+}
+
+// A simulated module chasing its commanded state under the motor limits
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct SwerveModule {
+    pub angle: Angle,
+    pub steer_velocity: AngularVelocity,
+    pub speed: Velocity,
+    pub target: SwerveModuleState,
+}
+
+impl Default for SwerveModuleConfig {
+    fn default() -> Self {
+        Self {
+            steer_max_velocity: AngularVelocity::new::<radian_per_second>(20.0),
+            steer_max_acceleration: AngularAcceleration::new::<radian_per_second_squared>(400.0),
+            drive_max_velocity: Velocity::new::<meter_per_second>(5.0),
+            drive_max_acceleration: Acceleration::new::<meter_per_second_squared>(50.0),
+        }
+    }
+}
+
+impl SwerveModule {
+    pub fn state(&self) -> SwerveModuleState {
+        SwerveModuleState::new(self.speed, self.angle)
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.speed != Velocity::ZERO || self.steer_velocity != AngularVelocity::ZERO
+    }
+
+    pub fn update(&mut self, config: &SwerveModuleConfig, dt: Time) {
+        let dt = dt.get::<second>();
+        if dt <= 0.0 {
+            return;
+        }
+
+        self.update_steer(config, dt);
+        self.update_drive(config, dt);
+    }
+
+    // Trapezoidal move towards the target angle, profiled from the current angle the short way round
+    fn update_steer(&mut self, config: &SwerveModuleConfig, dt: f32) {
+        let error = wrap_angle(self.target.angle.get::<radian>() - self.angle.get::<radian>());
+
+        let state = step_profile(
+            ProfileState { position: 0.0, velocity: self.steer_velocity.get::<radian_per_second>() },
+            error,
+            config.steer_max_velocity.get::<radian_per_second>(),
+            config.steer_max_acceleration.get::<radian_per_second_squared>(),
+            dt
+        );
+
+        self.angle = Angle::new::<radian>(wrap_angle(self.angle.get::<radian>() + state.position));
+        self.steer_velocity = AngularVelocity::new::<radian_per_second>(state.velocity);
+    }
+
+    fn update_drive(&mut self, config: &SwerveModuleConfig, dt: f32) {
+        let max_v = config.drive_max_velocity.get::<meter_per_second>();
+        let max_a = config.drive_max_acceleration.get::<meter_per_second_squared>();
+
+        let target = self.target.speed.get::<meter_per_second>().clamp(-max_v, max_v);
+        let v = self.speed.get::<meter_per_second>();
+
+        self.speed = Velocity::new::<meter_per_second>(v + (target - v).clamp(-max_a * dt, max_a * dt));
+    }
+}
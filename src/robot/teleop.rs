@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+
+use crate::layout::ui::{self, ConsoleRow};
+use crate::robot::kinematics::ChassisSpeeds;
+use crate::robot::odometry::Gyro;
+use crate::robot::{Robot, RobotConfig, RobotState};
+
+// Shapes stick deflection after the deadzone, keeping the sign
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    Squared,
+    Cubic,
+}
+
+pub struct TeleopSettings {
+    pub translation_deadzone: f32,
+    pub rotation_deadzone: f32,
+    pub translation_curve: ResponseCurve,
+    pub rotation_curve: ResponseCurve,
+    // Field-relative drives relative to the gyro heading at its last reset
+    pub field_relative: bool,
+}
+
+#[derive(Component)]
+pub struct TeleopStatusText;
+
+impl ResponseCurve {
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => x,
+            ResponseCurve::Squared => x * x.abs(),
+            ResponseCurve::Cubic => x * x * x,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ResponseCurve::Linear => ResponseCurve::Squared,
+            ResponseCurve::Squared => ResponseCurve::Cubic,
+            ResponseCurve::Cubic => ResponseCurve::Linear,
+        }
+    }
+}
+
+impl Default for TeleopSettings {
+    fn default() -> Self {
+        Self {
+            translation_deadzone: 0.1,
+            rotation_deadzone: 0.1,
+            translation_curve: ResponseCurve::Squared,
+            rotation_curve: ResponseCurve::Squared,
+            field_relative: true,
+        }
+    }
+}
+
+// Zero inside the deadzone, rescaled so the output still spans the full range outside it
+pub fn apply_deadzone(x: f32, deadzone: f32) -> f32 {
+    if x.abs() < deadzone {
+        0.0
+    } else {
+        x.signum() * ((x.abs() - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TeleopSettings::default());
+
+    commands.spawn_bundle(ui::text("", &ui::text_style(&asset_server, ui::SMALL_FONT_SIZE)))
+        .insert(TeleopStatusText)
+        .insert(ConsoleRow::Teleop);
+}
+
+// Left stick or WASD translates, right stick or QE rotates.
+// Select or F toggles field-relative driving, Start or R zeroes the gyro, North or C cycles the response curve.
+pub fn teleop_updater(
+    mut query: Query<(&mut Robot, &mut Gyro)>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<Input<GamepadButton>>,
+    config: Res<RobotConfig>,
+    mut settings: ResMut<TeleopSettings>
+) {
+    let (mut robot, mut gyro): (Mut<Robot>, Mut<Gyro>) = query.single_mut();
+
+    let gamepad = gamepads.iter().next().copied();
+    let axis = |t: GamepadAxisType| gamepad.and_then(|g| axes.get(GamepadAxis(g, t))).unwrap_or(0.0);
+    let button = |t: GamepadButtonType| gamepad.is_some_and(|g| buttons.just_pressed(GamepadButton(g, t)));
+
+    if button(GamepadButtonType::Select) || keyboard_input.just_pressed(KeyCode::F) {
+        settings.field_relative = !settings.field_relative;
+    }
+    if button(GamepadButtonType::Start) || keyboard_input.just_pressed(KeyCode::R) {
+        gyro.reset();
+    }
+    if button(GamepadButtonType::North) || keyboard_input.just_pressed(KeyCode::C) {
+        settings.translation_curve = settings.translation_curve.next();
+        settings.rotation_curve = settings.translation_curve;
+    }
+
+    // x forward (away from the driver), y left, z counterclockwise
+    let mut input = Vec3::new(
+        axis(GamepadAxisType::LeftStickY),
+        -axis(GamepadAxisType::LeftStickX),
+        -axis(GamepadAxisType::RightStickX),
+    );
+
+    if keyboard_input.pressed(KeyCode::W) { input.x = 1.0; }
+    if keyboard_input.pressed(KeyCode::S) { input.x = -1.0; }
+    if keyboard_input.pressed(KeyCode::A) { input.y = 1.0; }
+    if keyboard_input.pressed(KeyCode::D) { input.y = -1.0; }
+    if keyboard_input.pressed(KeyCode::Q) { input.z = 1.0; }
+    if keyboard_input.pressed(KeyCode::E) { input.z = -1.0; }
+
+    // The translation deadzone is radial so diagonals aren't snapped to the axes
+    let translation = Vec2::new(input.x, input.y);
+    let magnitude = settings.translation_curve.apply(apply_deadzone(translation.length().min(1.0), settings.translation_deadzone));
+    let translation = translation.normalize_or_zero() * magnitude;
+    let rotation = settings.rotation_curve.apply(apply_deadzone(input.z, settings.rotation_deadzone));
+
+    let vx = config.max_speed * translation.x;
+    let vy = config.max_speed * translation.y;
+    let omega = config.max_angular_velocity * rotation;
+
+    // The driver has no control during autonomous, and a disabled robot doesn't move at all
+    let command = match robot.state {
+        RobotState::Disabled | RobotState::Autonomous(_) => ChassisSpeeds::default(),
+        RobotState::Teleop if settings.field_relative => ChassisSpeeds::from_field_relative(vx, vy, omega, gyro.heading()),
+        RobotState::Teleop => ChassisSpeeds::new(vx, vy, omega),
+    };
+
+    if robot.command != command {
+        robot.command = command;
+    }
+}
+
+pub fn teleop_status_updater(
+    mut query: Query<&mut Text, With<TeleopStatusText>>,
+    gamepads: Res<Gamepads>,
+    settings: Res<TeleopSettings>
+) {
+    let value = format!(
+        "Drive: {}, {:?} response ({})",
+        if settings.field_relative { "field relative" } else { "robot relative" },
+        settings.translation_curve,
+        if gamepads.iter().next().is_some() { "gamepad" } else { "keyboard" }
+    );
+
+    for mut text in query.iter_mut() {
+        ui::set_text_if_changed(&mut text, value.clone());
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, Endpoint};
+use uom::si::angle::radian;
+use uom::si::angular_velocity::radian_per_second;
+use uom::si::acceleration::meter_per_second_squared;
+use uom::si::curvature::radian_per_meter;
+use uom::si::f32::{Acceleration, Angle, Curvature, Length, Time, Velocity};
+use uom::si::length::meter;
+use uom::si::time::second;
+use uom::si::velocity::meter_per_second;
+use crate::auto_pathing::trajectory::{TimedTrajectory, Trajectory, TrajectoryState};
+use crate::field::{FieldPose, FieldPosition};
+use crate::robot::RobotState;
+use crate::robot_connection::RobotClientError;
+
+mod proto {
+    tonic::include_proto!("robot_sim.v1");
+}
+
+use proto::robot_sim_client::RobotSimClient;
+
+const GRPC_ADDRESS: &str = "http://127.0.0.1:50051";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+// Same bound as the TCP backend's read timeout, a server that stops answering counts as disconnected
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Copy, Clone)]
+pub struct RemoteRobotState {
+    pub state: RobotState,
+    pub pose: FieldPose,
+}
+
+// Latest state received from StreamRobotState, written by a background task
+pub type RemoteRobotStateSlot = Arc<Mutex<Option<RemoteRobotState>>>;
+
+// Blocking wrapper around the generated async client, so it can be used like the TCP client
+pub struct GrpcRobotClient {
+    runtime: Runtime,
+    client: RobotSimClient<Channel>,
+    // Cleared when the robot state stream ends, which is how a server restart is noticed
+    stream_alive: Arc<AtomicBool>,
+}
+
+impl GrpcRobotClient {
+    pub fn connect(state_slot: RemoteRobotStateSlot) -> Result<Self, RobotClientError> {
+        let runtime = Runtime::new()?;
+
+        let channel = runtime.block_on(
+            Endpoint::from_static(GRPC_ADDRESS).connect_timeout(CONNECT_TIMEOUT).connect()
+        )?;
+
+        let mut client = Self {
+            runtime,
+            client: RobotSimClient::new(channel),
+            stream_alive: Arc::new(AtomicBool::new(false)),
+        };
+
+        client.stream_robot_state(state_slot)?;
+
+        Ok(client)
+    }
+
+    pub fn ping(&mut self) -> Result<(), RobotClientError> {
+        if self.stream_alive.load(Ordering::Relaxed) {
+            Ok(())
+        } else {
+            Err(RobotClientError::Protocol("robot state stream closed".to_string()))
+        }
+    }
+
+    pub fn gen_trajectory(&mut self, trajectory: &Trajectory) -> Result<TimedTrajectory, RobotClientError> {
+        let mut request = tonic::Request::new(proto::GenerateTrajectoryRequest {
+            trajectory: Some(proto::Trajectory {
+                start: Some(trajectory.start.into()),
+                points: trajectory.points.iter().map(|p| (*p).into()).collect(),
+                end: Some(trajectory.end.into()),
+                start_velocity: trajectory.start_velocity.get::<meter_per_second>() as f64,
+                end_velocity: trajectory.end_velocity.get::<meter_per_second>() as f64,
+                point_velocity_limits: trajectory.point_max_velocities.iter().enumerate()
+                    .filter_map(|(i, v)| v.map(|v| proto::PointVelocityLimit {
+                        point: i as u32,
+                        max_velocity: v.get::<meter_per_second>() as f64
+                    }))
+                    .collect(),
+                zones: trajectory.zones.iter().map(|z| proto::ConstraintZone {
+                    vertices: z.shape.vertices().into_iter().map(|v| v.into()).collect(),
+                    max_velocity: z.max_velocity.map_or(0.0, |v| v.get::<meter_per_second>() as f64),
+                    max_acceleration: z.max_acceleration.map_or(0.0, |a| a.get::<meter_per_second_squared>() as f64),
+                }).collect(),
+                rotation_targets: trajectory.rotation_targets.iter().enumerate()
+                    .filter_map(|(i, r)| r.map(|r| proto::RotationTarget {
+                        point: i as u32,
+                        rotation: r.get::<radian>() as f64
+                    }))
+                    .collect(),
+                config: Some(proto::TrajectoryConfig {
+                    max_velocity: trajectory.config.max_velocity.get::<meter_per_second>() as f64,
+                    max_acceleration: trajectory.config.max_acceleration.get::<meter_per_second_squared>() as f64,
+                    max_angular_velocity: trajectory.config.max_angular_velocity.get::<radian_per_second>() as f64,
+                }),
+            })
+        });
+        request.set_timeout(RESPONSE_TIMEOUT);
+
+        let response = self.runtime.block_on(self.client.generate_trajectory(request))?;
+
+        Ok(TimedTrajectory(response.into_inner().states.into_iter().map(|s| s.into()).collect()))
+    }
+
+    pub fn set_robot_mode(&mut self, state: RobotState) -> Result<(), RobotClientError> {
+        let (mode, routine) = match state {
+            RobotState::Disabled => (proto::RobotMode::Disabled, 0),
+            RobotState::Teleop => (proto::RobotMode::Teleop, 0),
+            RobotState::Autonomous(routine) => (proto::RobotMode::Autonomous, routine),
+        };
+
+        let mut request = tonic::Request::new(proto::SetRobotModeRequest {
+            mode: mode as i32,
+            routine
+        });
+        request.set_timeout(RESPONSE_TIMEOUT);
+
+        self.runtime.block_on(self.client.set_robot_mode(request))?;
+
+        Ok(())
+    }
+
+    // Subscribes to the robot state stream, keeping only the most recent state in the slot
+    fn stream_robot_state(&mut self, slot: RemoteRobotStateSlot) -> Result<(), RobotClientError> {
+        let mut stream = self.runtime
+            .block_on(self.client.stream_robot_state(proto::StreamRobotStateRequest {}))?
+            .into_inner();
+
+        let alive = self.stream_alive.clone();
+        alive.store(true, Ordering::Relaxed);
+
+        self.runtime.spawn(async move {
+            loop {
+                match stream.message().await {
+                    Ok(Some(state)) => {
+                        if let Some(state) = RemoteRobotState::from_proto(state) {
+                            *slot.lock().unwrap() = Some(state);
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        bevy::log::warn!("Robot state stream closed: {}", e);
+                        break;
+                    }
+                }
+            }
+            alive.store(false, Ordering::Relaxed);
+        });
+
+        Ok(())
+    }
+}
+
+impl RemoteRobotState {
+    fn from_proto(state: proto::RobotState) -> Option<Self> {
+        let state_enum = match proto::RobotMode::from_i32(state.mode)? {
+            proto::RobotMode::Unspecified => return None,
+            proto::RobotMode::Disabled => RobotState::Disabled,
+            proto::RobotMode::Teleop => RobotState::Teleop,
+            proto::RobotMode::Autonomous => RobotState::Autonomous(state.routine),
+        };
+
+        Some(Self {
+            state: state_enum,
+            pose: state.pose?.into()
+        })
+    }
+}
+
+impl From<FieldPosition> for proto::FieldPosition {
+    fn from(p: FieldPosition) -> Self {
+        Self {
+            x: p.x.get::<meter>() as f64,
+            y: p.y.get::<meter>() as f64,
+        }
+    }
+}
+
+impl From<proto::FieldPosition> for FieldPosition {
+    fn from(p: proto::FieldPosition) -> Self {
+        FieldPosition::new(Length::new::<meter>(p.x as f32), Length::new::<meter>(p.y as f32))
+    }
+}
+
+impl From<FieldPose> for proto::FieldPose {
+    fn from(p: FieldPose) -> Self {
+        Self {
+            translation: Some(p.translation.into()),
+            rotation: p.rotation.get::<radian>() as f64,
+        }
+    }
+}
+
+impl From<proto::FieldPose> for FieldPose {
+    fn from(p: proto::FieldPose) -> Self {
+        FieldPose::new(
+            p.translation.map(|t| t.into()).unwrap_or_default(),
+            Angle::new::<radian>(p.rotation as f32)
+        )
+    }
+}
+
+impl From<proto::TrajectoryState> for TrajectoryState {
+    fn from(s: proto::TrajectoryState) -> Self {
+        Self {
+            time: Time::new::<second>(s.time as f32),
+            pose: s.pose.map(|p| p.into()).unwrap_or_default(),
+            holonomic_rotation: Angle::new::<radian>(s.holonomic_rotation as f32),
+            velocity: Velocity::new::<meter_per_second>(s.velocity as f32),
+            acceleration: Acceleration::new::<meter_per_second_squared>(s.acceleration as f32),
+            curvature: Curvature::new::<radian_per_meter>(s.curvature as f32),
+        }
+    }
+}
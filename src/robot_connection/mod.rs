@@ -0,0 +1,376 @@
+mod grpc;
+mod tcp;
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use uom::si::f32::Length;
+use uom::si::length::inch;
+use crate::auto_pathing::trajectory::{TimedTrajectory, Trajectory};
+use crate::field::FieldPose;
+use crate::field::render::FieldZ;
+use crate::field::shapes::FieldRectangle;
+use crate::layout::ui::{self, ConsoleRow};
+use crate::robot::{Robot, RobotState};
+
+pub use grpc::{GrpcRobotClient, RemoteRobotStateSlot};
+pub use tcp::TcpRobotClient;
+
+pub struct RobotConnectionPlugin;
+
+// How often the background thread retries a connection or checks that it is still alive
+const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum RobotClientError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Protocol(String),
+    Server(String),
+    Grpc(Box<tonic::Status>),
+    Transport(tonic::transport::Error),
+    Disconnected,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RobotBackend {
+    Tcp,
+    Grpc,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ConnectionStatus {
+    Offline,
+    Connecting,
+    Connected,
+}
+
+pub enum RobotClient {
+    Tcp(TcpRobotClient),
+    Grpc(Box<GrpcRobotClient>),
+}
+
+// Handle to the (possibly absent) connection to the robot code, cheap to clone into tasks and threads.
+// A background thread keeps it connected, the app never blocks waiting for the server.
+#[derive(Clone)]
+pub struct RobotConnection {
+    backend: Option<RobotBackend>,
+    client: Arc<Mutex<Option<RobotClient>>>,
+    // Mirrors client.is_some() so the UI never waits on a lock held by a slow request
+    connected: Arc<AtomicBool>,
+    remote_state: RemoteRobotStateSlot,
+    // Mode changes for the background thread to send, so a frame never waits on the server
+    mode_sender: Arc<Mutex<Sender<RobotState>>>,
+    // Taken by the background thread when it starts
+    mode_receiver: Arc<Mutex<Option<Receiver<RobotState>>>>,
+}
+
+// The robot as reported by the robot code over StreamRobotState
+#[derive(Component)]
+pub struct RemoteRobot {
+    pub state: RobotState,
+}
+
+#[derive(Component)]
+pub struct ConnectionStatusText;
+
+impl Plugin for RobotConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup);
+        app.add_system(robot_mode_sync);
+        app.add_system(remote_robot_updater);
+        app.add_system(connection_status_updater);
+    }
+}
+
+impl RobotClient {
+    fn connect(backend: RobotBackend, remote_state: &RemoteRobotStateSlot) -> Result<Self, RobotClientError> {
+        Ok(match backend {
+            RobotBackend::Tcp => RobotClient::Tcp(TcpRobotClient::connect()?),
+            RobotBackend::Grpc => RobotClient::Grpc(Box::new(GrpcRobotClient::connect(remote_state.clone())?)),
+        })
+    }
+
+    pub fn gen_trajectory(&mut self, trajectory: &Trajectory) -> Result<TimedTrajectory, RobotClientError> {
+        match self {
+            RobotClient::Tcp(c) => c.gen_trajectory(trajectory),
+            RobotClient::Grpc(c) => c.gen_trajectory(trajectory),
+        }
+    }
+
+    fn ping(&mut self) -> Result<(), RobotClientError> {
+        match self {
+            RobotClient::Tcp(c) => c.ping(),
+            RobotClient::Grpc(c) => c.ping(),
+        }
+    }
+
+    // Only the gRPC robot code has modes
+    fn set_robot_mode(&mut self, state: RobotState) -> Result<(), RobotClientError> {
+        match self {
+            RobotClient::Tcp(_) => Ok(()),
+            RobotClient::Grpc(c) => c.set_robot_mode(state),
+        }
+    }
+}
+
+impl RobotConnection {
+    pub fn new(backend: Option<RobotBackend>) -> Self {
+        let (mode_sender, mode_receiver) = mpsc::channel();
+        Self {
+            backend,
+            client: Arc::new(Mutex::new(None)),
+            connected: Arc::new(AtomicBool::new(false)),
+            remote_state: RemoteRobotStateSlot::default(),
+            mode_sender: Arc::new(Mutex::new(mode_sender)),
+            mode_receiver: Arc::new(Mutex::new(Some(mode_receiver))),
+        }
+    }
+
+    pub fn backend(&self) -> Option<RobotBackend> {
+        self.backend
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        match self.backend {
+            None => ConnectionStatus::Offline,
+            Some(_) if self.connected.load(Ordering::Relaxed) => ConnectionStatus::Connected,
+            Some(_) => ConnectionStatus::Connecting,
+        }
+    }
+
+    // Runs f against the connected client, dropping the client if the connection turned out to be broken
+    pub fn with_client<T>(&self, f: impl FnOnce(&mut RobotClient) -> Result<T, RobotClientError>) -> Result<T, RobotClientError> {
+        let mut client = self.client.lock().unwrap();
+
+        let result = match client.as_mut() {
+            Some(c) => f(c),
+            None => return Err(RobotClientError::Disconnected),
+        };
+
+        if let Err(e) = &result {
+            if e.is_connection_error() {
+                warn!("Lost connection to robot: {}", e);
+                *client = None;
+                self.connected.store(false, Ordering::Relaxed);
+            }
+        }
+
+        result
+    }
+
+    pub fn gen_trajectory(&self, trajectory: &Trajectory) -> Result<TimedTrajectory, RobotClientError> {
+        self.with_client(|c| c.gen_trajectory(trajectory))
+    }
+
+    fn spawn_connector(&self) {
+        let backend = match self.backend {
+            Some(b) => b,
+            None => return
+        };
+
+        let mode_receiver = match self.mode_receiver.lock().unwrap().take() {
+            Some(r) => r,
+            None => return
+        };
+
+        let connection = self.clone();
+
+        thread::spawn(move || {
+            // Latest mode from the app, resent after every reconnect
+            let mut mode: Option<RobotState> = None;
+            let mut mode_sent = false;
+
+            loop {
+                if connection.connected.load(Ordering::Relaxed) {
+                    match mode {
+                        Some(m) if !mode_sent => {
+                            match connection.with_client(|c| c.set_robot_mode(m)) {
+                                Ok(()) | Err(RobotClientError::Disconnected) => {}
+                                Err(e) => warn!("Unable to set robot mode: {}", e),
+                            }
+                            mode_sent = true;
+                        }
+                        _ => {
+                            let _ = connection.with_client(|c| c.ping());
+                        }
+                    }
+                } else if let Ok(client) = RobotClient::connect(backend, &connection.remote_state) {
+                    info!("Connected to the robot");
+                    *connection.client.lock().unwrap() = Some(client);
+                    connection.connected.store(true, Ordering::Relaxed);
+                    mode_sent = false;
+                }
+
+                // Waiting on the channel instead of sleeping sends mode changes right away
+                match mode_receiver.recv_timeout(CONNECTION_CHECK_INTERVAL) {
+                    Ok(m) => {
+                        mode = Some(m);
+                        mode_sent = false;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(CONNECTION_CHECK_INTERVAL),
+                }
+            }
+        });
+    }
+
+    // Queues a mode change for the background thread, never blocks on the connection
+    pub fn set_robot_mode(&self, state: RobotState) {
+        let _ = self.mode_sender.lock().unwrap().send(state);
+    }
+}
+
+impl RobotClientError {
+    // Whether the connection can no longer be trusted, as opposed to a single failed request
+    fn is_connection_error(&self) -> bool {
+        match self {
+            RobotClientError::Server(_) => false,
+            RobotClientError::Disconnected => false,
+            RobotClientError::Grpc(status) => matches!(
+                status.code(),
+                tonic::Code::Unavailable | tonic::Code::Unknown | tonic::Code::Cancelled | tonic::Code::DeadlineExceeded
+            ),
+            _ => true,
+        }
+    }
+}
+
+fn setup(mut commands: Commands, connection: Res<RobotConnection>, asset_server: Res<AssetServer>) {
+    connection.spawn_connector();
+
+    commands.spawn_bundle(ui::text("", &ui::text_style(&asset_server, ui::SMALL_FONT_SIZE)))
+        .insert(ConnectionStatusText)
+        .insert(ConsoleRow::Connection);
+
+    if connection.backend() != Some(RobotBackend::Grpc) {
+        return;
+    }
+
+    commands.insert_resource(connection.remote_state.clone());
+
+    commands
+        .spawn_bundle(GeometryBuilder::build_as(
+            &shapes::Rectangle::default(),
+            DrawMode::Stroke(StrokeMode::new(remote_robot_color(RobotState::Disabled), 3.0)),
+            Transform::default(),
+        ))
+        .insert(FieldPose::default())
+        .insert(FieldRectangle {
+            width: Length::new::<inch>(29.0),
+            height: Length::new::<inch>(29.0),
+            origin: RectangleOrigin::Center,
+        })
+        .insert(RemoteRobot {
+            state: RobotState::Disabled,
+        })
+        .insert(FieldZ::ROBOT);
+}
+
+fn remote_robot_color(state: RobotState) -> Color {
+    match state {
+        RobotState::Disabled => Color::GRAY,
+        RobotState::Teleop => Color::LIME_GREEN,
+        RobotState::Autonomous(_) => Color::ORANGE,
+    }
+}
+
+// Mirrors local mode changes to the robot code, the background thread sends them and resends the mode after a reconnect
+fn robot_mode_sync(
+    query: Query<&Robot>,
+    connection: Res<RobotConnection>,
+    mut last_state: Local<Option<RobotState>>
+) {
+    for robot in query.iter() {
+        // The Robot component also changes while driving, only mode changes are sent
+        if *last_state == Some(robot.state) {
+            continue;
+        }
+        *last_state = Some(robot.state);
+
+        connection.set_robot_mode(robot.state);
+    }
+}
+
+fn remote_robot_updater(
+    mut query: Query<(&mut RemoteRobot, &mut FieldPose, &mut DrawMode)>,
+    slot: Option<Res<RemoteRobotStateSlot>>
+) {
+    let latest = match slot.and_then(|s| s.lock().unwrap().take()) {
+        Some(s) => s,
+        None => return
+    };
+
+    for i in query.iter_mut() {
+        let (mut remote_robot, mut pose, mut draw_mode): (Mut<RemoteRobot>, Mut<FieldPose>, Mut<DrawMode>) = i;
+        remote_robot.state = latest.state;
+        *pose = latest.pose;
+        *draw_mode = DrawMode::Stroke(StrokeMode::new(remote_robot_color(remote_robot.state), 3.0));
+    }
+}
+
+fn connection_status_updater(mut query: Query<&mut Text, With<ConnectionStatusText>>, connection: Res<RobotConnection>) {
+    let backend = match connection.backend() {
+        Some(RobotBackend::Tcp) => "tcp",
+        Some(RobotBackend::Grpc) => "grpc",
+        None => "",
+    };
+
+    let (value, color) = match connection.status() {
+        ConnectionStatus::Offline => ("Robot: offline, native trajectories".to_string(), Color::GRAY),
+        ConnectionStatus::Connecting => (format!("Robot: connecting ({})...", backend), Color::ORANGE),
+        ConnectionStatus::Connected => (format!("Robot: connected ({})", backend), Color::LIME_GREEN),
+    };
+
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+            text.sections[0].style.color = color;
+        }
+    }
+}
+
+impl fmt::Display for RobotClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RobotClientError::Io(e) => write!(f, "connection error: {}", e),
+            RobotClientError::Json(e) => write!(f, "malformed message: {}", e),
+            RobotClientError::Protocol(e) => write!(f, "protocol error: {}", e),
+            RobotClientError::Server(e) => write!(f, "server error: {}", e),
+            RobotClientError::Grpc(e) => write!(f, "grpc error: {}", e.message()),
+            RobotClientError::Transport(e) => write!(f, "transport error: {}", e),
+            RobotClientError::Disconnected => write!(f, "not connected"),
+        }
+    }
+}
+
+impl std::error::Error for RobotClientError {}
+
+impl From<std::io::Error> for RobotClientError {
+    fn from(e: std::io::Error) -> Self {
+        RobotClientError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RobotClientError {
+    fn from(e: serde_json::Error) -> Self {
+        RobotClientError::Json(e)
+    }
+}
+
+impl From<tonic::Status> for RobotClientError {
+    fn from(e: tonic::Status) -> Self {
+        RobotClientError::Grpc(Box::new(e))
+    }
+}
+
+impl From<tonic::transport::Error> for RobotClientError {
+    fn from(e: tonic::transport::Error) -> Self {
+        RobotClientError::Transport(e)
+    }
+}
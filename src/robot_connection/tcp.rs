@@ -0,0 +1,125 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::auto_pathing::trajectory::{TimedTrajectory, Trajectory, TrajectoryState};
+use crate::robot_connection::RobotClientError;
+
+// Bumped whenever the message layout changes, must match robot_comm_server.py
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Frames larger than this are treated as a corrupt stream rather than allocated
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+const TCP_ADDRESS: ([u8; 4], u16) = ([127, 0, 0, 1], 65426);
+
+#[derive(Serialize, Deserialize)]
+pub struct Message<T> {
+    pub version: u32,
+    pub id: u64,
+    pub body: T,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum Request<'a> {
+    GenerateTrajectory { trajectory: &'a Trajectory },
+    Ping,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum Response {
+    Trajectory { states: Vec<TrajectoryState> },
+    Pong,
+    Error { message: String },
+}
+
+pub struct TcpRobotClient {
+    stream: TcpStream,
+    next_id: u64,
+}
+
+impl TcpRobotClient {
+    pub fn connect() -> Result<Self, RobotClientError> {
+        let stream = TcpStream::connect_timeout(&SocketAddr::from(TCP_ADDRESS), CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+
+        Ok(Self {
+            stream,
+            next_id: 0
+        })
+    }
+
+    pub fn gen_trajectory(&mut self, trajectory: &Trajectory) -> Result<TimedTrajectory, RobotClientError> {
+        match self.request(Request::GenerateTrajectory { trajectory })? {
+            Response::Trajectory { states } => Ok(TimedTrajectory(states)),
+            Response::Error { message } => Err(RobotClientError::Server(message)),
+            _ => Err(RobotClientError::Protocol("unexpected response to GenerateTrajectory".to_string())),
+        }
+    }
+
+    pub fn ping(&mut self) -> Result<(), RobotClientError> {
+        match self.request(Request::Ping)? {
+            Response::Pong => Ok(()),
+            Response::Error { message } => Err(RobotClientError::Server(message)),
+            _ => Err(RobotClientError::Protocol("unexpected response to Ping".to_string())),
+        }
+    }
+
+    fn request(&mut self, body: Request) -> Result<Response, RobotClientError> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.write_frame(&serde_json::to_vec(&Message { version: PROTOCOL_VERSION, id, body })?)?;
+
+        loop {
+            let frame = self.read_frame()?;
+            let message: Message<Response> = serde_json::from_slice(&frame)?;
+
+            if message.version != PROTOCOL_VERSION {
+                return Err(RobotClientError::Protocol(format!(
+                    "server speaks protocol version {}, expected {}", message.version, PROTOCOL_VERSION
+                )));
+            }
+
+            // Responses to earlier requests that timed out are dropped
+            if message.id < id {
+                continue;
+            }
+
+            if message.id != id {
+                return Err(RobotClientError::Protocol(format!(
+                    "response id {} does not match request id {}", message.id, id
+                )));
+            }
+
+            return Ok(message.body);
+        }
+    }
+
+    // Frames are a big-endian u32 byte length followed by a JSON payload
+    fn write_frame(&mut self, payload: &[u8]) -> Result<(), RobotClientError> {
+        self.stream.write_all(&(payload.len() as u32).to_be_bytes())?;
+        self.stream.write_all(payload)?;
+        Ok(())
+    }
+
+    fn read_frame(&mut self) -> Result<Vec<u8>, RobotClientError> {
+        let mut len_buf = [0; 4];
+        self.stream.read_exact(&mut len_buf)?;
+
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(RobotClientError::Protocol(format!("frame of {} bytes exceeds limit", len)));
+        }
+
+        let mut buf = vec![0; len];
+        self.stream.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}